@@ -0,0 +1,31 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use scavenger::game::Obstruction;
+use scavenger::game::fov::field_of_view;
+use scavenger::game::geometry::Position;
+
+// a fixed mix of walls and trees, roughly as cluttered as a forest level
+fn obstruction(pos: Position) -> Obstruction {
+    let hash = (pos.x.wrapping_mul(73_856_093) ^ pos.y.wrapping_mul(19_349_663)).rem_euclid(10);
+    match hash {
+        0 => Obstruction::Full,
+        1 | 2 => Obstruction::Partial,
+        _ => Obstruction::None,
+    }
+}
+
+fn bench_fov(c: &mut Criterion) {
+    let mut group = c.benchmark_group("field_of_view");
+    for &radius in &[3, 8, 16] {
+        group.bench_with_input(BenchmarkId::new("open", radius), &radius, |b, &radius| {
+            b.iter(|| field_of_view(Position { x: 0, y: 0 }, black_box(radius), |_| Obstruction::None))
+        });
+        group.bench_with_input(BenchmarkId::new("cluttered", radius), &radius, |b, &radius| {
+            b.iter(|| field_of_view(Position { x: 0, y: 0 }, black_box(radius), obstruction))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_fov);
+criterion_main!(benches);
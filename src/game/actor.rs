@@ -1,26 +1,115 @@
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use super::{Action, ActionError, Entity, EntityType, Game, TileView};
-use super::geometry::{Direction, Position};
+use super::{Action, ActionError, ActorType, Entity, Game, GameEvent, Idle, Movement, TileView};
+use super::geometry::{ALL_DIRECTIONS, Direction, Position};
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
-pub enum ActorType {
-    // TODO: more creatures
-    Player,
-    Rat,
-    Wolf,
-    Crab,
-    Beetle,
-    BigJelly,
-    LittleJelly,
-    Ghost,
-    Dragonfly,
-}
-
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+/// Energy needed to take one action; an actor with this speed acts once per turn.
+pub(super) const ACTION_COST: u32 = 12;
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Status {
+    Hasted,
+    Slowed,
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub status: Status,
+    pub turns: u32,
+}
+
+// how far from home wandering monsters go
+const WANDER_RADIUS: i32 = 5;
+// how far from the player's last known position monsters look around
+const SEARCH_RADIUS: i32 = 2;
+const SEARCH_TURNS: u32 = 6;
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub(super) enum ActorState {
     Wait,
-    Pursue(Position),
+    /// Ambling around near home.
+    Wander,
+    /// Keeping watch at home, and heading back there when there's nothing else to do.
+    Guard,
+    /// Heading for where the player was last seen, this many turns ago.
+    Pursue { target: Position, unseen: u32 },
+    /// Poking around where the player was last seen for a few more turns.
+    Search { target: Position, turns: u32 },
+    /// Running away from where the player was last seen, this many turns ago.
+    Flee { from: Position, unseen: u32 },
+}
+
+/// What a monster is up to, without the details.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum AiState {
+    Wait,
+    Wander,
+    Guard,
+    Pursue,
+    Search,
+    Flee,
+}
+
+impl AiState {
+    pub fn name(self) -> &'static str {
+        match self {
+            AiState::Wait => "wait",
+            AiState::Wander => "wander",
+            AiState::Guard => "guard",
+            AiState::Pursue => "pursue",
+            AiState::Search => "search",
+            AiState::Flee => "flee",
+        }
+    }
+}
+
+/// Where a monster hangs around, and what it does there when it isn't bothered by the player.
+/// Usually that comes from its definition, but some are put somewhere to guard it.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub(super) struct Home {
+    pub(super) pos: Position,
+    pub(super) idle: ActorState,
+}
+
+impl ActorState {
+    /// What a kind of creature usually does when it isn't bothered by the player.
+    pub(super) fn idle(actor: ActorType) -> ActorState {
+        match actor.def().idle {
+            Idle::Wait => ActorState::Wait,
+            Idle::Wander => ActorState::Wander,
+            Idle::Guard => ActorState::Guard,
+        }
+    }
+
+    pub(super) fn ai_state(self) -> AiState {
+        match self {
+            ActorState::Wait => AiState::Wait,
+            ActorState::Wander => AiState::Wander,
+            ActorState::Guard => AiState::Guard,
+            ActorState::Pursue { .. } => AiState::Pursue,
+            ActorState::Search { .. } => AiState::Search,
+            ActorState::Flee { .. } => AiState::Flee,
+        }
+    }
+
+    pub(super) fn is_idle(self) -> bool {
+        matches!(self, ActorState::Wait | ActorState::Wander | ActorState::Guard)
+    }
+}
+
+impl Game {
+    pub(super) fn set_state(&mut self, e: Entity, state: ActorState) {
+        let old = match self.states.insert(e, state) {
+            Some(old) => old.ai_state(),
+            None => { return; }
+        };
+        if old != state.ai_state() {
+            if let (Some(actor), Some(&pos)) = (self.actor_type(e), self.positions.get(&e)) {
+                self.events.push(GameEvent::AiChanged { actor, pos, from: old, to: state.ai_state() });
+            }
+        }
+    }
 }
 
 fn path(rng: &mut impl Rng, from: Position, to: Position) -> Option<[Direction; 4]> {
@@ -53,103 +142,412 @@ fn path(rng: &mut impl Rng, from: Position, to: Position) -> Option<[Direction;
     })
 }
 
-fn move_towards(g: &mut Game, e: Entity, pos: Position) {
+// Monsters only attack when they're after the player, not when they happen to bump into them.
+fn move_towards(g: &mut Game, e: Entity, pos: Position, attack: bool) {
     if let Some(&epos) = g.positions.get(&e) {
         if let Some(dirs) = path(&mut g.rng, epos, pos) {
             for &dir in &dirs {
-                if let Ok(_) = g.take_action(e, Action::MoveAttack(dir)) {
+                let action = if attack { Action::MoveAttack(dir) } else { Action::Move(dir) };
+                if g.take_action(e, action).is_ok() {
                     return;
                 }
             }
         }
     }
-    g.states.insert(e, ActorState::Wait);
 }
 
-fn knights_move(g: &mut Game, e: Entity, pos: Position) {
+/// A random step that stays within some distance of a point, or heads back towards it.
+fn amble(g: &mut Game, e: Entity, movement: Movement, center: Position, radius: i32) {
+    let epos = match g.positions.get(&e) {
+        Some(&pos) => pos,
+        None => { return; }
+    };
+    let target = Position {
+        x: center.x + g.rng.gen_range(-radius, radius + 1),
+        y: center.y + g.rng.gen_range(-radius, radius + 1),
+    };
+    if epos.chebyshev_distance(center) > radius {
+        step_towards(g, e, movement, center, false);
+    } else if epos != target {
+        step_towards(g, e, movement, target, false);
+    }
+}
+
+/// Takes the shortest way to somewhere, or just heads in its general direction if there's no
+/// way there.
+fn step_towards(g: &mut Game, e: Entity, movement: Movement, pos: Position, attack: bool) {
+    let (actor_type, epos) = match (g.actor_type(e), g.positions.get(&e).cloned()) {
+        (Some(a), Some(epos)) => (a, epos),
+        _ => { return; }
+    };
+    let steps = g.next_steps(actor_type, epos, pos);
+    if steps.is_empty() {
+        match movement {
+            Movement::Knight => knights_move(g, e, pos, attack),
+            Movement::Any | Movement::Orthogonal => move_towards(g, e, pos, attack),
+        }
+    } else if movement == Movement::Knight {
+        jump(g, e, &steps, attack);
+    } else {
+        for step in steps {
+            let dir = match ALL_DIRECTIONS.iter().find(|&&dir| epos.step(dir) == step) {
+                Some(&dir) => dir,
+                None => { continue; }
+            };
+            let action = if attack { Action::MoveAttack(dir) } else { Action::Move(dir) };
+            if g.take_action(e, action).is_ok() {
+                return;
+            }
+        }
+    }
+}
+
+/// Moves to whichever neighboring tile is furthest from a point, fighting back if cornered.
+fn flee(g: &mut Game, e: Entity, from: Position) {
+    let epos = match g.positions.get(&e) {
+        Some(&pos) => pos,
+        None => { return; }
+    };
+    let mut dirs = ALL_DIRECTIONS;
+    dirs.shuffle(&mut g.rng);
+    dirs.sort_by_key(|&dir| -epos.step(dir).chebyshev_distance(from));
+    let dist = epos.chebyshev_distance(from);
+    for &dir in &dirs {
+        if epos.step(dir).chebyshev_distance(from) > dist && g.take_action(e, Action::Move(dir)).is_ok() {
+            return;
+        }
+    }
+    if let Some(player_pos) = g.player_position() {
+        if epos.adjacent_to(player_pos) {
+            move_towards(g, e, player_pos, true);
+        }
+    }
+}
+
+fn knights_move(g: &mut Game, e: Entity, pos: Position, attack: bool) {
     if let Some(&epos) = g.positions.get(&e) {
-        let mut dests = {
-            use Direction::*;
-            [
-                epos.step(North).step(NorthWest),
-                epos.step(North).step(NorthEast),
-                epos.step(East).step(NorthEast),
-                epos.step(East).step(SouthEast),
-                epos.step(South).step(SouthEast),
-                epos.step(South).step(SouthWest),
-                epos.step(West).step(SouthWest),
-                epos.step(West).step(NorthWest),
-            ]
-        };
+        let mut dests = super::path::knight_moves(epos);
         let dist = epos.chebyshev_distance(pos);
         dests.shuffle(&mut g.rng);
         // TODO: sort by distance rather than partitioning?
         dests.sort_by_key(|p| p.chebyshev_distance(pos) > dist);
-        for &dest in &dests {
-            if !super::fov::has_los(g, epos, dest) {
-                continue;
-            }
-            match g.set_actor_position(e, dest) {
-                Ok(_) => { return; }
-                Err(ActionError::Occupied) => {
-                    if Some(dest) == g.player_position() {
-                        if let Ok(_) = g.kill_actor(super::PLAYER) {
-                            let _ = g.set_actor_position(e, dest);
-                            return;
-                        }
+        let dests: Vec<_> = dests.iter().cloned()
+            .filter(|&dest| super::fov::has_los(g, epos, dest))
+            .collect();
+        jump(g, e, &dests, attack);
+    }
+}
+
+/// Jumps to the first of some places that's free, or attacks the player if they're in the way.
+fn jump(g: &mut Game, e: Entity, dests: &[Position], attack: bool) {
+    for &dest in dests {
+        match g.set_actor_position(e, dest) {
+            Ok(_) => { return; }
+            Err(ActionError::Occupied) => {
+                if attack && Some(dest) == g.player_position() {
+                    if let Some(attacker) = g.actor_type(e) {
+                        g.hit(e, super::PLAYER, attacker.damage());
+                    }
+                    if !g.is_player_alive() {
+                        let _ = g.set_actor_position(e, dest);
                     }
+                    return;
                 }
-                Err(_) => {}
             }
+            Err(_) => {}
         }
     }
-    g.states.insert(e, ActorState::Wait);
 }
 
-pub(super) fn take_actions(g: &mut Game) {
-    // TODO: randomize order
-    for (e, state) in g.states.clone() {
-        let actor_type = match g.types.get(&e).cloned() {
-            Some(EntityType::Actor(a)) => a,
-            _ => { continue; }
-        };
-        match state {
-            ActorState::Wait => {}
-            ActorState::Pursue(pos) => match actor_type {
-                ActorType::Player => {}
-                ActorType::Wolf => {
-                    move_towards(g, e, pos);
-                    move_towards(g, e, pos);
-                }
-                ActorType::Dragonfly => {
-                    knights_move(g, e, pos);
+fn speed(g: &Game, e: Entity) -> u32 {
+    let base = match g.actor_type(e) {
+        Some(a) => a.speed(),
+        None => { return 0; }
+    };
+    let speed = g.statuses.get(&e).into_iter().flatten().fold(base, |speed, effect| match effect.status {
+        Status::Hasted => speed * 2,
+        Status::Slowed => speed / 2,
+    });
+    speed.max(1)
+}
+
+fn act(g: &mut Game, e: Entity) {
+    let actor_type = match g.actor_type(e) {
+        Some(a) => a,
+        None => { return; }
+    };
+    if actor_type == ActorType::PLAYER {
+        return;
+    }
+    let movement = actor_type.def().movement;
+    let home = g.homes.get(&e).map(|home| home.pos);
+    match g.states.get(&e).cloned() {
+        None | Some(ActorState::Wait) => {}
+        Some(ActorState::Wander) => {
+            // wandering is a leisurely business. The roll is made even without a home to wander
+            // around, so that the random numbers come out the same either way
+            let ambles = g.rng.gen_ratio(1, 3);
+            if let Some(home) = home {
+                if ambles {
+                    amble(g, e, movement, home, WANDER_RADIUS);
                 }
-                _ => {
-                    move_towards(g, e, pos);
+            }
+        }
+        Some(ActorState::Guard) => {
+            if let Some(home) = home {
+                if g.positions.get(&e) != Some(&home) {
+                    step_towards(g, e, movement, home, false);
                 }
             }
         }
+        Some(ActorState::Pursue { target, .. }) => step_towards(g, e, movement, target, true),
+        Some(ActorState::Search { target, .. }) => amble(g, e, movement, target, SEARCH_RADIUS),
+        Some(ActorState::Flee { from, .. }) => flee(g, e, from),
+    }
+}
+
+fn tick(g: &mut Game) {
+    let actors: Vec<Entity> = std::iter::once(super::PLAYER).chain(g.states.keys().cloned()).collect();
+    for &e in &actors {
+        let speed = speed(g, e);
+        *g.energy.entry(e).or_insert(0) += speed;
+    }
+    for effects in g.statuses.values_mut() {
+        for effect in effects.iter_mut() {
+            effect.turns = effect.turns.saturating_sub(1);
+        }
+        effects.retain(|effect| effect.turns > 0);
+    }
+    g.statuses.retain(|_, effects| !effects.is_empty());
+
+    // Everyone who's ready acts once in a random order, then repeat for anyone fast enough to act
+    // again. Sleeping monsters still spend their energy so they can't save it up.
+    loop {
+        let mut ready: Vec<Entity> = g.states.keys().cloned()
+            .filter(|e| g.energy.get(e).cloned().unwrap_or(0) >= ACTION_COST)
+            .collect();
+        if ready.is_empty() {
+            break;
+        }
+        ready.shuffle(&mut g.rng);
+        for e in ready {
+            if let Some(energy) = g.energy.get_mut(&e) {
+                *energy -= ACTION_COST;
+                act(g, e);
+            }
+        }
     }
 }
 
+/// Runs monster turns until the player has enough energy to act again.
+pub(super) fn take_actions(g: &mut Game) {
+    g.distance_maps.clear();
+    if let Some(energy) = g.energy.get_mut(&super::PLAYER) {
+        *energy = energy.saturating_sub(ACTION_COST);
+    }
+    while g.is_player_alive()
+        && g.energy.get(&super::PLAYER).cloned().unwrap_or(0) < ACTION_COST
+    {
+        tick(g);
+    }
+}
+
+/// Whether a monster can see the player from where it is.
+fn can_see_player(g: &Game, actor_type: ActorType, pos: Position, player_pos: Position) -> bool {
+    let radius = actor_type.def().sight_radius;
+    super::fov::in_range(player_pos.x - pos.x, player_pos.y - pos.y, radius)
+        && super::fov::has_los(g, pos, player_pos)
+}
+
+/// Monsters look for the player and decide what to do next. Ones that don't know about the
+/// player yet might not notice them, especially if they're sneaking. Ones that do chase them,
+/// search where they were last seen, and eventually lose interest and go back to what they
+/// were doing. Anything which flees when hurt runs away instead.
 pub(super) fn notice_player(g: &mut Game) {
     let player_pos = match g.player_position() {
         Some(pos) => pos,
         None => { return; }
     };
 
-    for (e, s) in g.states.iter_mut() {
-        let actor_type = match g.types.get(&e).cloned() {
-            Some(EntityType::Actor(a)) => a,
+    let monsters: Vec<Entity> = g.states.keys().cloned().collect();
+    for e in monsters {
+        let (actor_type, pos) = match (g.actor_type(e), g.positions.get(&e).cloned()) {
+            (Some(a), Some(pos)) if a != ActorType::PLAYER => (a, pos),
             _ => { continue; }
         };
-        if actor_type == ActorType::Player {
-            continue;
-        }
-        if let Some(pos) = g.positions.get(&e).cloned() {
-            if let Some(&TileView::Visible { .. }) = g.view.get(&pos) {
-                *s = ActorState::Pursue(player_pos)
+        let def = actor_type.def();
+        // only things the player can see get mentioned
+        let visible = matches!(g.view(pos), TileView::Visible { actor: Some(_), .. });
+        let sees = can_see_player(g, actor_type, pos, player_pos);
+        let hurt = g.health.get(&e).is_some_and(|&hp| hp <= def.flees_below);
+        let idle = match g.homes.get(&e) {
+            Some(home) => home.idle,
+            None => ActorState::idle(actor_type),
+        };
+        let state = match g.states.get(&e) {
+            Some(&state) => state,
+            None => { continue; }
+        };
+        let new_state = match state {
+            _ if sees && hurt && !state.is_idle() => ActorState::Flee { from: player_pos, unseen: 0 },
+            ActorState::Flee { unseen, .. } if unseen >= def.memory => idle,
+            ActorState::Flee { from, unseen } => ActorState::Flee { from, unseen: unseen + 1 },
+            ActorState::Pursue { .. } if sees => ActorState::Pursue { target: player_pos, unseen: 0 },
+            ActorState::Pursue { unseen, .. } if unseen >= def.memory => {
+                if visible {
+                    g.events.push(GameEvent::LostTrack { actor: actor_type, pos });
+                }
+                idle
+            }
+            ActorState::Pursue { target, .. } if pos == target => ActorState::Search { target, turns: SEARCH_TURNS },
+            ActorState::Pursue { target, unseen } => ActorState::Pursue { target, unseen: unseen + 1 },
+            ActorState::Search { .. } if sees => {
+                if visible {
+                    g.events.push(GameEvent::NoticedPlayer { actor: actor_type, pos });
+                }
+                ActorState::Pursue { target: player_pos, unseen: 0 }
+            }
+            ActorState::Search { turns: 0, .. } => {
+                if visible {
+                    g.events.push(GameEvent::LostTrack { actor: actor_type, pos });
+                }
+                idle
             }
+            ActorState::Search { target, turns } => ActorState::Search { target, turns: turns - 1 },
+            _ if sees => {
+                let chance = if g.sneaking { def.alertness / 2 } else { def.alertness };
+                // nothing stays oblivious to the player right next to it
+                if pos.adjacent_to(player_pos) || g.rng.gen_ratio(chance.min(100), 100) {
+                    if visible {
+                        g.events.push(GameEvent::NoticedPlayer { actor: actor_type, pos });
+                    }
+                    if hurt {
+                        ActorState::Flee { from: player_pos, unseen: 0 }
+                    } else {
+                        ActorState::Pursue { target: player_pos, unseen: 0 }
+                    }
+                } else {
+                    state
+                }
+            }
+            _ => state,
+        };
+        g.set_state(e, new_state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{PLAYER, Tile};
+
+    // the player at the origin of a big empty field with nothing else on it
+    fn arena(seed: u64) -> Game {
+        let mut g = Game::new(seed);
+        g.tiles.clear();
+        for x in -30..=30 {
+            for y in -30..=30 {
+                g.tiles.insert(Position { x, y }, Tile::Ground);
+            }
+        }
+        g.states.clear();
+        g.homes.clear();
+        g.objects.clear();
+        g.positions.retain(|&e, _| e == PLAYER);
+        g.actors.retain(|_, &mut e| e == PLAYER);
+        g
+    }
+
+    fn spawn(g: &mut Game, name: &str, pos: Position) -> Entity {
+        g.spawn_actor(ActorType::named(name).unwrap(), pos).unwrap()
+    }
+
+    fn state(g: &Game, e: Entity) -> ActorState {
+        g.states[&e]
+    }
+
+    // far enough away that nothing can see the player
+    fn hide_player(g: &mut Game) {
+        g.set_actor_position(PLAYER, Position { x: 25, y: 25 }).unwrap();
+    }
+
+    #[test]
+    fn guards_go_back_to_guarding_after_searching() {
+        let mut g = arena(0);
+        let pos = Position { x: 1, y: 0 };
+        let wolf = spawn(&mut g, "wolf", pos);
+        g.states.insert(wolf, ActorState::Guard);
+        g.homes.insert(wolf, Home { pos, idle: ActorState::Guard });
+
+        notice_player(&mut g);
+        let target = Position { x: 0, y: 0 };
+        assert_eq!(state(&g, wolf), ActorState::Pursue { target, unseen: 0 });
+
+        hide_player(&mut g);
+        g.set_actor_position(wolf, target).unwrap();
+        notice_player(&mut g);
+        assert_eq!(state(&g, wolf), ActorState::Search { target, turns: SEARCH_TURNS });
+        for _ in 0..SEARCH_TURNS {
+            notice_player(&mut g);
+            assert_eq!(state(&g, wolf).ai_state(), AiState::Search);
+        }
+        notice_player(&mut g);
+        assert_eq!(state(&g, wolf), ActorState::Guard);
+    }
+
+    #[test]
+    fn hurt_monsters_flee() {
+        let mut g = arena(0);
+        let rat = spawn(&mut g, "rat", Position { x: 1, y: 0 });
+        notice_player(&mut g);
+        assert_eq!(state(&g, rat).ai_state(), AiState::Pursue);
+
+        g.health.insert(rat, 1);
+        notice_player(&mut g);
+        assert_eq!(state(&g, rat), ActorState::Flee { from: Position { x: 0, y: 0 }, unseen: 0 });
+
+        hide_player(&mut g);
+        let memory = ActorType::named("rat").unwrap().def().memory;
+        for _ in 0..memory {
+            notice_player(&mut g);
+            assert_eq!(state(&g, rat).ai_state(), AiState::Flee);
+        }
+        notice_player(&mut g);
+        assert_eq!(state(&g, rat), ActorState::Wander);
+    }
+
+    #[test]
+    fn pursuers_give_up_after_a_while() {
+        let mut g = arena(0);
+        let wolf = spawn(&mut g, "wolf", Position { x: 1, y: 0 });
+        notice_player(&mut g);
+        assert_eq!(state(&g, wolf).ai_state(), AiState::Pursue);
+
+        hide_player(&mut g);
+        let memory = ActorType::named("wolf").unwrap().def().memory;
+        for _ in 0..memory {
+            notice_player(&mut g);
+            assert_eq!(state(&g, wolf).ai_state(), AiState::Pursue);
         }
+        notice_player(&mut g);
+        assert_eq!(state(&g, wolf), ActorState::Wander);
+    }
+
+    #[test]
+    fn sneaking_is_harder_to_notice() {
+        let noticed = |sneaking| {
+            let mut g = arena(0);
+            g.sneaking = sneaking;
+            // in sight but not right next to the player
+            let wolf = spawn(&mut g, "wolf", Position { x: 3, y: 0 });
+            (0..1000).filter(|_| {
+                g.set_state(wolf, ActorState::Wait);
+                notice_player(&mut g);
+                state(&g, wolf).ai_state() == AiState::Pursue
+            }).count()
+        };
+        let (normal, sneaking) = (noticed(false), noticed(true));
+        assert!(sneaking < normal * 3 / 4, "noticed {} times sneaking, {} not", sneaking, normal);
+        assert!(sneaking > 0);
     }
 }
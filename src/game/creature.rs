@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::ItemType;
+
+const BUILTIN_CREATURES: &str = include_str!("../../data/creatures.ron");
+
+static CREATURES: OnceLock<Creatures> = OnceLock::new();
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Deserialize)]
+pub enum Movement {
+    /// One step in any of the eight directions.
+    #[default]
+    Any,
+    /// No diagonal moves, and no diagonal attacks either way.
+    Orthogonal,
+    /// Jumps like a chess knight, over anything it can see past.
+    Knight,
+}
+
+/// What a creature gets up to before it notices the player, and goes back to afterwards.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Deserialize)]
+pub enum Idle {
+    #[default]
+    Wait,
+    /// Wanders around near where it started.
+    Wander,
+    /// Stays where it started.
+    Guard,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct SpawnRules {
+    /// How likely this creature is to be picked compared to the others.
+    pub weight: u32,
+    #[serde(default)]
+    pub more_common_deeper: bool,
+    /// Smallest and largest number that turn up together.
+    #[serde(default = "default_group_size")]
+    pub group_size: (u32, u32),
+    /// Groups per level which are always spawned, whatever the weights say.
+    #[serde(default)]
+    pub min_count: u32,
+    #[serde(default)]
+    pub max_count: Option<u32>,
+    /// How close to the player's starting point a group can be.
+    #[serde(default = "default_min_distance")]
+    pub min_distance: i32,
+}
+
+fn default_group_size() -> (u32, u32) {
+    (1, 1)
+}
+
+fn default_min_distance() -> i32 {
+    3
+}
+
+fn default_speed() -> u32 {
+    super::actor::ACTION_COST
+}
+
+fn default_sight_radius() -> i32 {
+    super::fov::SIGHT_RADIUS
+}
+
+fn default_alertness() -> u32 {
+    100
+}
+
+fn default_memory() -> u32 {
+    8
+}
+
+fn default_corpse_color() -> String {
+    "light red".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreatureDef {
+    pub name: String,
+    pub glyph: char,
+    pub color: String,
+    #[serde(default = "default_corpse_color")]
+    pub corpse_color: String,
+    #[serde(default = "default_speed")]
+    pub speed: u32,
+    pub max_hp: u32,
+    pub damage: u32,
+    #[serde(default)]
+    pub armor: u32,
+    #[serde(default)]
+    pub movement: Movement,
+    #[serde(default = "default_sight_radius")]
+    pub sight_radius: i32,
+    /// Percent chance each turn of noticing the player when they're in sight, halved if the
+    /// player is sneaking.
+    #[serde(default = "default_alertness")]
+    pub alertness: u32,
+    /// Turns spent looking for the player after losing sight of them before giving up.
+    #[serde(default = "default_memory")]
+    pub memory: u32,
+    #[serde(default)]
+    pub idle: Idle,
+    /// Runs from the player once down to this many hit points.
+    #[serde(default)]
+    pub flees_below: u32,
+    /// Gets knocked back a tile by the player's attacks, crushing whatever is behind it.
+    #[serde(default)]
+    pub pushed_on_hit: bool,
+    #[serde(default)]
+    pub invisible_until_adjacent: bool,
+    /// Can cross deep water.
+    #[serde(default)]
+    pub swims: bool,
+    /// Can open and close doors, which takes an action.
+    #[serde(default)]
+    pub opens_doors: bool,
+    /// Goes straight through closed doors.
+    #[serde(default)]
+    pub passes_doors: bool,
+    #[serde(default = "default_true")]
+    pub leaves_corpse: bool,
+    /// Name of the creature that appears on each side of this one when it dies.
+    #[serde(default)]
+    pub splits_into: Option<String>,
+    #[serde(default)]
+    pub loot: Option<ItemType>,
+    #[serde(default)]
+    pub harvest: Option<ItemType>,
+    #[serde(default)]
+    pub spawn: Option<SpawnRules>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CreatureError {
+    Malformed(String),
+    Invalid(String),
+    AlreadyLoaded,
+}
+
+struct Creatures {
+    defs: Vec<CreatureDef>,
+    splits_into: Vec<Option<ActorType>>,
+    by_name: HashMap<String, ActorType>,
+}
+
+impl Creatures {
+    fn parse(ron: &str) -> Result<Creatures, CreatureError> {
+        let defs: Vec<CreatureDef> = ron::de::from_str(ron)
+            .map_err(|e| CreatureError::Malformed(e.to_string()))?;
+        match defs.first() {
+            Some(def) if def.name == "player" => {}
+            _ => { return Err(CreatureError::Invalid("the first creature must be the player".to_string())); }
+        }
+        let mut by_name = HashMap::new();
+        for (idx, def) in defs.iter().enumerate() {
+            if def.max_hp == 0 {
+                return Err(CreatureError::Invalid(format!("{} has no hit points", def.name)));
+            }
+            if let Some(SpawnRules { group_size: (min, max), min_count, max_count, .. }) = def.spawn {
+                if min == 0 || min > max {
+                    return Err(CreatureError::Invalid(format!("{} has a bad group size", def.name)));
+                }
+                if max_count.is_some_and(|max_count| min_count > max_count) {
+                    return Err(CreatureError::Invalid(format!("{} has a min count over its max count", def.name)));
+                }
+            }
+            if by_name.insert(def.name.clone(), ActorType(idx as u16)).is_some() {
+                return Err(CreatureError::Invalid(format!("{} is defined twice", def.name)));
+            }
+        }
+        let splits_into = defs.iter().map(|def| match &def.splits_into {
+            Some(name) => match by_name.get(name) {
+                Some(&actor) => Ok(Some(actor)),
+                None => Err(CreatureError::Invalid(format!("{} splits into unknown creature {}", def.name, name))),
+            },
+            None => Ok(None),
+        }).collect::<Result<_, _>>()?;
+        Ok(Creatures { defs, splits_into, by_name })
+    }
+}
+
+fn creatures() -> &'static Creatures {
+    CREATURES.get_or_init(|| Creatures::parse(BUILTIN_CREATURES).expect("invalid built in creatures"))
+}
+
+/// Replaces the built in creature definitions. This has to happen before any game is
+/// created, and saves or replays from games with different creatures won't load properly.
+pub fn load_creatures(ron: &str) -> Result<(), CreatureError> {
+    let creatures = Creatures::parse(ron)?;
+    CREATURES.set(creatures).map_err(|_| CreatureError::AlreadyLoaded)
+}
+
+#[derive(Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct ActorType(u16);
+
+impl ActorType {
+    pub const PLAYER: ActorType = ActorType(0);
+
+    /// All creatures in the order they were defined.
+    pub fn all() -> impl Iterator<Item = ActorType> {
+        (0..creatures().defs.len()).map(|idx| ActorType(idx as u16))
+    }
+
+    pub fn named(name: &str) -> Option<ActorType> {
+        creatures().by_name.get(name).cloned()
+    }
+
+    pub fn def(self) -> &'static CreatureDef {
+        &creatures().defs[self.0 as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        &self.def().name
+    }
+
+    pub fn speed(self) -> u32 {
+        self.def().speed
+    }
+
+    pub fn max_hp(self) -> u32 {
+        self.def().max_hp
+    }
+
+    pub fn damage(self) -> u32 {
+        self.def().damage
+    }
+
+    pub fn armor(self) -> u32 {
+        self.def().armor
+    }
+
+    pub fn splits_into(self) -> Option<ActorType> {
+        creatures().splits_into[self.0 as usize]
+    }
+}
+
+impl fmt::Debug for ActorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ActorType({})", self.name())
+    }
+}
+
+// saved by name so that saves survive creatures being added or reordered
+impl Serialize for ActorType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for ActorType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ActorType, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        ActorType::named(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown creature {}", name)))
+    }
+}
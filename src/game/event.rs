@@ -0,0 +1,25 @@
+use super::{ActorType, AiState, ItemType};
+use super::geometry::Position;
+
+/// Something that happened during a turn, in the order it happened.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GameEvent {
+    Moved { actor: ActorType, from: Position, to: Position },
+    Attacked { attacker: ActorType, target: ActorType, pos: Position, damage: u32 },
+    Killed { victim: ActorType, killer: Option<ActorType>, pos: Position },
+    Pushed { actor: ActorType, from: Position, to: Position },
+    Split { actor: ActorType, pos: Position },
+    Revealed { actor: ActorType, pos: Position },
+    NoticedPlayer { actor: ActorType, pos: Position },
+    LostTrack { actor: ActorType, pos: Position },
+    /// A monster changed what it's doing. Only shown when debugging.
+    AiChanged { actor: ActorType, pos: Position, from: AiState, to: AiState },
+    Sneaking { sneaking: bool },
+    PickedUp { item: ItemType },
+    Dropped { actor: ActorType, item: ItemType, pos: Position },
+    Used { item: ItemType },
+    Harvested { corpse: ActorType, item: ItemType },
+    ChangedLevel { from: u32, to: u32 },
+    OpenedDoor { actor: ActorType, pos: Position },
+    ClosedDoor { actor: ActorType, pos: Position },
+}
@@ -1,63 +1,97 @@
-use super::{EntityType, Game, Obstruction, Tile, TileView};
+use super::{EntityType, Game, GameEvent, Obstruction, Tile, TileView};
 use super::geometry::{ORTHOGONAL_DIRECTIONS, Position};
-use super::actor::{ActorState, ActorType};
+use super::actor::ActorState;
 
-enum RulePt {
-    F(i32, i32),
-    P(i32, i32),
-}
+/// How far the player can see.
+pub const SIGHT_RADIUS: i32 = 3;
 
-static FOV_RULES: &'static [(&'static [RulePt], (i32, i32))] = {
-    use RulePt::*;
-    &[
-        (&[], (1, 0)),
-        (&[P(1, 0)], (1, 1)),
-        (&[F(1, 0)], (2, 0)),
-        (&[P(0, 1), F(1, 1)], (2, 1)),
-        (&[P(1, 0), F(1, 1)], (2, 1)),
-        (&[F(1, 0), P(1, 1)], (2, 1)),
-        (&[F(1, 0), P(2, 0)], (2, 1)),
-        (&[P(1, 0), F(1, 1), P(2, 1)], (2, 2)),
-        (&[P(0, 1), F(1, 1), P(2, 1)], (2, 2)),
-        (&[F(1, 0), F(2, 0)], (3, 0)),
-        (&[F(1, 0), P(2, 0), F(2, 1)], (3, 1)),
-        (&[F(1, 0), P(1, 1), F(2, 1)], (3, 1)),
-    ]
-};
-
-// TODO: optimize this shit
-pub fn has_los(g: &mut Game, pos1: Position, pos2: Position) -> bool {
-    let mappings: &[fn((i32, i32)) -> (i32, i32)] = &[
-        |(x, y)| (x, y),
-        |(x, y)| (x, -y),
-        |(x, y)| (-x, y),
-        |(x, y)| (-x, -y),
-        |(x, y)| (y, x),
-        |(x, y)| (y, -x),
-        |(x, y)| (-y, x),
-        |(x, y)| (-y, -x),
-    ];
+/// Whether an offset is close enough to be seen from the given radius away. This rounds the
+/// circle out a bit so that it isn't spiky at small radii.
+pub fn in_range(dx: i32, dy: i32, radius: i32) -> bool {
+    dx * dx + dy * dy <= radius * radius + radius
+}
 
-    use RulePt::*;
-    for mapping in mappings {
-        let to_pos = |pt| {
-            let (dx, dy) = mapping(pt);
-            Position { x: pos1.x + dx, y: pos1.y + dy }
+/// Whether one tile can be seen from another, whatever the distance.
+///
+/// The sight line is the line between the two tile centers, rounded to one tile per column (or
+/// row, for mostly vertical lines); where it passes exactly between two tiles either one will
+/// do. Every tile on it between the two ends has to be completely clear, and each diagonal
+/// step has to squeeze past at least one side which isn't fully blocked. So trees block sight
+/// when they're right in the way, but not when they're only at the corners. This is symmetric:
+/// if one tile can see another, that one can see it back.
+pub fn has_line_of_sight(from: Position, to: Position, obstruction: impl Fn(Position) -> Obstruction) -> bool {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let steps = dx.abs().max(dy.abs());
+    // the position on the line after some number of steps, rounding both ways on a tie
+    let line_at = |i: i32| -> [Position; 2] {
+        let round = |d: i32| {
+            let (t, n) = (i * d.abs(), steps);
+            let (q, r) = (t / n, t % n);
+            let (low, high) = match (2 * r).cmp(&n) {
+                std::cmp::Ordering::Less => (q, q),
+                std::cmp::Ordering::Equal => (q, q + 1),
+                std::cmp::Ordering::Greater => (q + 1, q + 1),
+            };
+            (low * d.signum(), high * d.signum())
         };
-        for &(ray, pt) in FOV_RULES {
-            if pos2 == to_pos(pt) && ray.iter().all(|pt| match pt {
-                &F(dx, dy) => g.tile(to_pos((dx, dy))).obstruction() == Obstruction::None,
-                &P(dx, dy) => g.tile(to_pos((dx, dy))).obstruction() != Obstruction::Full,
-            }) {
-                return true;
+        let ((x1, x2), (y1, y2)) = (round(dx), round(dy));
+        [
+            Position { x: from.x + x1, y: from.y + y1 },
+            Position { x: from.x + x2, y: from.y + y2 },
+        ]
+    };
+    let mut reached = [from, from];
+    for i in 1..=steps {
+        // at most both of the candidates for this step, so there's no need to allocate
+        let mut now_reached = [from, from];
+        let mut count = 0;
+        for &pos in &line_at(i) {
+            if now_reached[..count].contains(&pos) || pos != to && obstruction(pos) != Obstruction::None {
+                continue;
+            }
+            let can_step = |prev: Position| {
+                prev.chebyshev_distance(pos) == 1 && (prev.x == pos.x || prev.y == pos.y
+                    || obstruction(Position { x: prev.x, y: pos.y }) != Obstruction::Full
+                    || obstruction(Position { x: pos.x, y: prev.y }) != Obstruction::Full)
+            };
+            if reached.iter().any(|&prev| can_step(prev)) {
+                now_reached[count] = pos;
+                count += 1;
             }
         }
+        reached = match count {
+            0 => { return false; }
+            1 => [now_reached[0], now_reached[0]],
+            _ => now_reached,
+        };
     }
+    true
+}
 
-    false
+/// Every tile that can be seen from the center within a radius, including the center itself.
+///
+/// This checks each tile in range with `has_line_of_sight` rather than shadowcasting, since
+/// monsters use that to see the player and the two have to agree exactly, ties, trees and
+/// diagonal squeezes included. It's O(r³), but only runs once a turn at `SIGHT_RADIUS`: the
+/// `fov` bench takes about 0.5µs at radius 3, 6-7µs at 8 and 35-50µs at 16.
+pub fn field_of_view(center: Position, radius: i32, obstruction: impl Fn(Position) -> Obstruction) -> Vec<Position> {
+    let mut visible = Vec::new();
+    for dx in -radius..=radius {
+        for dy in -radius..=radius {
+            let pos = Position { x: center.x + dx, y: center.y + dy };
+            if in_range(dx, dy, radius) && has_line_of_sight(center, pos, &obstruction) {
+                visible.push(pos);
+            }
+        }
+    }
+    visible
 }
 
-pub fn update_view(g: &mut Game) {
+pub(super) fn has_los(g: &Game, pos1: Position, pos2: Position) -> bool {
+    has_line_of_sight(pos1, pos2, |pos| g.tile(pos).obstruction())
+}
+
+pub(super) fn update_view(g: &mut Game) {
     for v in g.view.values_mut() {
         if let &mut TileView::Visible { object, tile, .. } = v {
             *v = TileView::Remembered { object, tile };
@@ -75,14 +109,17 @@ pub fn update_view(g: &mut Game) {
         // TODO: ghost should maybe go invisible again once no longer in FOV
         let actor_type = if let Some(&actor) = g.actors.get(&pos) {
             if let Some(&EntityType::Actor(actor_type)) = g.types.get(&actor) {
-                if actor_type == ActorType::Ghost {
-                    if g.visible_ghosts.contains(&actor) {
-                        Some(ActorType::Ghost)
+                if actor_type.def().invisible_until_adjacent {
+                    if g.revealed_actors.contains(&actor) {
+                        Some(actor_type)
                     } else {
                         if pos.adjacent_to(player_pos) {
-                            g.states.insert(actor, ActorState::Wait);
-                            g.visible_ghosts.insert(actor);
-                            Some(ActorType::Ghost)
+                            // it goes back to whatever it was doing before it was spotted
+                            let idle = g.homes.get(&actor).map_or(ActorState::idle(actor_type), |home| home.idle);
+                            g.set_state(actor, idle);
+                            g.revealed_actors.insert(actor);
+                            g.events.push(GameEvent::Revealed { actor: actor_type, pos });
+                            Some(actor_type)
                         } else {
                             None
                         }
@@ -92,45 +129,26 @@ pub fn update_view(g: &mut Game) {
                 }
             } else { None }
         } else { None };
+        let wounded = match (actor_type, g.actors.get(&pos)) {
+            (Some(t), Some(actor)) => g.health.get(actor).cloned().unwrap_or(0) < t.max_hp(),
+            _ => false,
+        };
         g.view.insert(pos, TileView::Visible {
             actor: actor_type,
+            wounded,
             object: g.objects.get(&pos).and_then(|v| v.last())
                 .and_then(|&e| g.types.get(&e).cloned()),
             tile: g.tile(pos),
         });
     };
 
-    mark_visible(g, player_pos);
-
-    let mappings: &[fn((i32, i32)) -> (i32, i32)] = &[
-        |(x, y)| (x, y),
-        |(x, y)| (x, -y),
-        |(x, y)| (-x, y),
-        |(x, y)| (-x, -y),
-        |(x, y)| (y, x),
-        |(x, y)| (y, -x),
-        |(x, y)| (-y, x),
-        |(x, y)| (-y, -x),
-    ];
-
-    use RulePt::*;
-    for mapping in mappings {
-        let to_pos = |pt| {
-            let (dx, dy) = mapping(pt);
-            Position { x: player_pos.x + dx, y: player_pos.y + dy }
-        };
-        for &(ray, pt) in FOV_RULES {
-            if ray.iter().all(|pt| match pt {
-                &F(dx, dy) => g.tile(to_pos((dx, dy))).obstruction() == Obstruction::None,
-                &P(dx, dy) => g.tile(to_pos((dx, dy))).obstruction() != Obstruction::Full,
-            }) {
-                mark_visible(g, to_pos(pt));
-            }
-        }
+    for pos in field_of_view(player_pos, SIGHT_RADIUS, |pos| g.tile(pos).obstruction()) {
+        mark_visible(g, pos);
     }
 
-    for dx in -4..=4 {
-        for dy in -4..=4 {
+    let border = SIGHT_RADIUS + 1;
+    for dx in -border..=border {
+        for dy in -border..=border {
             let pos = Position { x: player_pos.x + dx, y: player_pos.y + dy };
             if g.view(pos) == TileView::Unknown {
                 for &dir in &ORTHOGONAL_DIRECTIONS {
@@ -153,3 +171,132 @@ pub fn update_view(g: &mut Game) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use rand::prelude::*;
+    use crate::game::rng::GameRng;
+
+    enum RulePt {
+        F(i32, i32),
+        P(i32, i32),
+    }
+
+    // the hand written rules this replaced, for one octant
+    static OLD_RULES: &[(&[RulePt], (i32, i32))] = {
+        use RulePt::*;
+        &[
+            (&[], (1, 0)),
+            (&[P(1, 0)], (1, 1)),
+            (&[F(1, 0)], (2, 0)),
+            (&[P(0, 1), F(1, 1)], (2, 1)),
+            (&[P(1, 0), F(1, 1)], (2, 1)),
+            (&[F(1, 0), P(1, 1)], (2, 1)),
+            (&[F(1, 0), P(2, 0)], (2, 1)),
+            (&[P(1, 0), F(1, 1), P(2, 1)], (2, 2)),
+            (&[P(0, 1), F(1, 1), P(2, 1)], (2, 2)),
+            (&[F(1, 0), F(2, 0)], (3, 0)),
+            (&[F(1, 0), P(2, 0), F(2, 1)], (3, 1)),
+            (&[F(1, 0), P(1, 1), F(2, 1)], (3, 1)),
+        ]
+    };
+
+    type Mapping = fn((i32, i32)) -> (i32, i32);
+
+    static MAPPINGS: &[Mapping] = &[
+        |(x, y)| (x, y),
+        |(x, y)| (x, -y),
+        |(x, y)| (-x, y),
+        |(x, y)| (-x, -y),
+        |(x, y)| (y, x),
+        |(x, y)| (y, -x),
+        |(x, y)| (-y, x),
+        |(x, y)| (-y, -x),
+    ];
+
+    fn old_field_of_view(obstruction: impl Fn(Position) -> Obstruction) -> Vec<Position> {
+        use RulePt::*;
+        let mut visible = vec![Position { x: 0, y: 0 }];
+        for mapping in MAPPINGS {
+            let to_pos = |pt| {
+                let (x, y) = mapping(pt);
+                Position { x, y }
+            };
+            for &(ray, pt) in OLD_RULES {
+                if ray.iter().all(|pt| match *pt {
+                    F(dx, dy) => obstruction(to_pos((dx, dy))) == Obstruction::None,
+                    P(dx, dy) => obstruction(to_pos((dx, dy))) != Obstruction::Full,
+                }) {
+                    visible.push(to_pos(pt));
+                }
+            }
+        }
+        visible.sort();
+        visible.dedup();
+        visible
+    }
+
+    fn random_map(rng: &mut impl Rng, radius: i32) -> HashMap<Position, Obstruction> {
+        let mut map = HashMap::new();
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                let obstruction = match rng.gen_range(0, 3) {
+                    0 => Obstruction::Full,
+                    1 => Obstruction::Partial,
+                    _ => Obstruction::None,
+                };
+                map.insert(Position { x, y }, obstruction);
+            }
+        }
+        map
+    }
+
+    fn lookup(map: &HashMap<Position, Obstruction>) -> impl Fn(Position) -> Obstruction + '_ {
+        move |pos| map.get(&pos).cloned().unwrap_or(Obstruction::Full)
+    }
+
+    #[test]
+    fn open_ground_is_a_circle() {
+        let visible = field_of_view(Position { x: 0, y: 0 }, SIGHT_RADIUS, |_| Obstruction::None);
+        assert_eq!(visible.len(), 37);
+    }
+
+    #[test]
+    fn matches_old_rules() {
+        let mut rng = GameRng::seed_from_u64(0);
+        for _ in 0..20000 {
+            let map = random_map(&mut rng, 4);
+            let mut visible = field_of_view(Position { x: 0, y: 0 }, 3, lookup(&map));
+            visible.sort();
+            assert_eq!(visible, old_field_of_view(lookup(&map)), "{:?}", map);
+        }
+    }
+
+    #[test]
+    fn symmetric() {
+        let mut rng = GameRng::seed_from_u64(1);
+        for _ in 0..2000 {
+            let map = random_map(&mut rng, 8);
+            let (from, to) = (Position { x: rng.gen_range(-8, 9), y: rng.gen_range(-8, 9) },
+                              Position { x: rng.gen_range(-8, 9), y: rng.gen_range(-8, 9) });
+            assert_eq!(has_line_of_sight(from, to, lookup(&map)), has_line_of_sight(to, from, lookup(&map)),
+                       "{:?} {:?} {:?}", from, to, map);
+        }
+    }
+
+    #[test]
+    fn trees_block_sight_only_when_in_the_way() {
+        let origin = Position { x: 0, y: 0 };
+        let tree_at = |tree: Position| move |pos| if pos == tree { Obstruction::Partial } else { Obstruction::None };
+        assert!(!has_line_of_sight(origin, Position { x: 5, y: 0 }, tree_at(Position { x: 2, y: 0 })));
+        assert!(has_line_of_sight(origin, Position { x: 5, y: 0 }, tree_at(Position { x: 2, y: 1 })));
+        let walls = |pos: Position| if pos.x == 1 && pos.y == 0 || pos.x == 0 && pos.y == 1 {
+            Obstruction::Full
+        } else {
+            Obstruction::None
+        };
+        assert!(!has_line_of_sight(origin, Position { x: 3, y: 3 }, walls));
+    }
+}
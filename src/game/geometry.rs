@@ -1,4 +1,6 @@
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Direction {
     North,
     NorthEast,
@@ -83,7 +85,7 @@ impl Direction {
     }
 }
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
@@ -110,4 +112,15 @@ impl Position {
     pub fn chebyshev_distance(self, o: Position) -> i32 {
         i32::max((self.x - o.x).abs(), (self.y - o.y).abs())
     }
+
+    /// Turns around the origin so that north ends up facing the given way. Diagonal
+    /// directions round down to the orthogonal one before them.
+    pub fn rotate(self, facing: Direction) -> Position {
+        (0..facing.to_index() / 2).fold(self, |pos, _| Position { x: -pos.y, y: pos.x })
+    }
+
+    /// Flips east and west.
+    pub fn mirror(self) -> Position {
+        Position { x: -self.x, y: self.y }
+    }
 }
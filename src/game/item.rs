@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+use super::{ActionError, ActionResult, ActorType, Entity, EntityType, Game, GameEvent, Status};
+use super::geometry::Position;
+
+pub(super) const INVENTORY_SIZE: usize = 10;
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ItemType {
+    Meat,
+    Jelly,
+    Ectoplasm,
+}
+
+impl Game {
+    pub fn inventory(&self) -> Vec<ItemType> {
+        self.inventory.iter().filter_map(|e| match self.types.get(e) {
+            Some(&EntityType::Item(item)) => Some(item),
+            _ => None,
+        }).collect()
+    }
+
+    fn item_type(&self, e: Entity) -> Option<ItemType> {
+        match self.types.get(&e) {
+            Some(&EntityType::Item(item)) => Some(item),
+            _ => None,
+        }
+    }
+
+    pub(super) fn place_item(&mut self, item: ItemType, pos: Position) {
+        let e = self.new_entity(EntityType::Item(item));
+        self.objects.entry(pos).or_default().push(e);
+    }
+
+    fn player_pos(&self, e: Entity) -> ActionResult<Position> {
+        // only the player gets an inventory
+        if e != super::PLAYER {
+            return Err(ActionError::InvalidActor);
+        }
+        self.positions.get(&e).cloned().ok_or(ActionError::InvalidActor)
+    }
+
+    pub(super) fn pick_up(&mut self, e: Entity) -> ActionResult {
+        let pos = self.player_pos(e)?;
+        if self.inventory.len() >= INVENTORY_SIZE {
+            return Err(ActionError::InventoryFull);
+        }
+        let objects = self.objects.get(&pos).cloned().unwrap_or_default();
+        let (idx, item) = objects.iter().enumerate().rev()
+            .find_map(|(idx, &o)| self.item_type(o).map(|item| (idx, item)))
+            .ok_or(ActionError::NoItem)?;
+        if let Some(objects) = self.objects.get_mut(&pos) {
+            self.inventory.push(objects.remove(idx));
+        }
+        self.events.push(GameEvent::PickedUp { item });
+        Ok(())
+    }
+
+    pub(super) fn drop_item(&mut self, e: Entity, idx: usize) -> ActionResult {
+        let pos = self.player_pos(e)?;
+        if idx >= self.inventory.len() {
+            return Err(ActionError::InvalidItem);
+        }
+        let item_entity = self.inventory.remove(idx);
+        self.objects.entry(pos).or_default().push(item_entity);
+        if let Some(item) = self.item_type(item_entity) {
+            self.events.push(GameEvent::Dropped { actor: ActorType::PLAYER, item, pos });
+        }
+        Ok(())
+    }
+
+    pub(super) fn use_item(&mut self, e: Entity, idx: usize) -> ActionResult {
+        self.player_pos(e)?;
+        let item = self.inventory.get(idx).and_then(|&i| self.item_type(i))
+            .ok_or(ActionError::InvalidItem)?;
+        let item_entity = self.inventory.remove(idx);
+        self.types.remove(&item_entity);
+        let max_hp = ActorType::PLAYER.max_hp();
+        match item {
+            ItemType::Meat => {
+                let hp = self.health.entry(e).or_insert(0);
+                *hp = (*hp + 4).min(max_hp);
+            }
+            ItemType::Jelly => {
+                self.add_status(e, Status::Hasted, 10);
+            }
+            ItemType::Ectoplasm => {
+                self.health.insert(e, max_hp);
+            }
+        }
+        self.events.push(GameEvent::Used { item });
+        Ok(())
+    }
+
+    pub(super) fn harvest(&mut self, e: Entity) -> ActionResult {
+        let pos = self.player_pos(e)?;
+        let objects = self.objects.get(&pos).cloned().unwrap_or_default();
+        let (idx, corpse, item) = objects.iter().enumerate().rev()
+            .find_map(|(idx, o)| match self.types.get(o) {
+                Some(&EntityType::Corpse(actor)) => actor.def().harvest.map(|item| (idx, actor, item)),
+                _ => None,
+            })
+            .ok_or(ActionError::NoItem)?;
+        if let Some(objects) = self.objects.get_mut(&pos) {
+            let corpse_entity = objects.remove(idx);
+            self.types.remove(&corpse_entity);
+            self.positions.remove(&corpse_entity);
+        }
+        self.place_item(item, pos);
+        self.events.push(GameEvent::Harvested { corpse, item });
+        Ok(())
+    }
+}
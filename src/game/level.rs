@@ -0,0 +1,158 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+
+use super::{ActionError, ActionResult, Entity, Game, GameEvent, PLAYER, Tile, TileView};
+use super::ActorType;
+use super::actor::{ActorState, Home, StatusEffect};
+use super::geometry::{ALL_DIRECTIONS, Position};
+use super::map;
+
+/// Everything belonging to a level the player isn't currently on. Nothing happens there
+/// until the player comes back.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(super) struct Level {
+    pub(super) tiles: HashMap<Position, Tile>,
+    pub(super) states: BTreeMap<Entity, ActorState>,
+    pub(super) positions: HashMap<Entity, Position>,
+    pub(super) actors: HashMap<Position, Entity>,
+    pub(super) objects: HashMap<Position, Vec<Entity>>,
+    pub(super) revealed_actors: HashSet<Entity>,
+    pub(super) health: HashMap<Entity, u32>,
+    pub(super) energy: HashMap<Entity, u32>,
+    pub(super) statuses: HashMap<Entity, Vec<StatusEffect>>,
+    pub(super) homes: HashMap<Entity, Home>,
+    pub(super) view: HashMap<Position, TileView>,
+}
+
+impl Game {
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    // Takes the current level out of the game, leaving only the player behind.
+    fn stash_level(&mut self) -> Level {
+        let mut level = Level {
+            tiles: mem::take(&mut self.tiles),
+            states: mem::take(&mut self.states),
+            positions: mem::take(&mut self.positions),
+            actors: mem::take(&mut self.actors),
+            objects: mem::take(&mut self.objects),
+            revealed_actors: mem::take(&mut self.revealed_actors),
+            health: mem::take(&mut self.health),
+            energy: mem::take(&mut self.energy),
+            statuses: mem::take(&mut self.statuses),
+            homes: mem::take(&mut self.homes),
+            view: mem::take(&mut self.view),
+        };
+        if let Some(pos) = level.positions.remove(&PLAYER) {
+            level.actors.remove(&pos);
+        }
+        if let Some(hp) = level.health.remove(&PLAYER) {
+            self.health.insert(PLAYER, hp);
+        }
+        if let Some(energy) = level.energy.remove(&PLAYER) {
+            self.energy.insert(PLAYER, energy);
+        }
+        if let Some(statuses) = level.statuses.remove(&PLAYER) {
+            self.statuses.insert(PLAYER, statuses);
+        }
+        level
+    }
+
+    fn restore_level(&mut self, level: Level) {
+        self.tiles = level.tiles;
+        self.states = level.states;
+        self.positions = level.positions;
+        self.actors = level.actors;
+        self.objects = level.objects;
+        self.revealed_actors = level.revealed_actors;
+        self.health.extend(level.health);
+        self.energy.extend(level.energy);
+        self.statuses.extend(level.statuses);
+        self.homes = level.homes;
+        self.view = level.view;
+    }
+
+    /// Where the player ends up when arriving by the given stairs: on them if possible,
+    /// otherwise next to them.
+    fn arrival_position(
+        tiles: &HashMap<Position, Tile>,
+        actors: &HashMap<Position, Entity>,
+        stairs: Tile,
+    ) -> Option<Position> {
+        let stairs_pos = tiles.iter()
+            .filter(|&(_, &tile)| tile == stairs)
+            .map(|(&pos, _)| pos)
+            .min()?;
+        let is_free = |pos: Position| {
+            tiles.get(&pos).is_some_and(|t| t.passable_for(ActorType::PLAYER))
+                && !actors.contains_key(&pos)
+        };
+        if is_free(stairs_pos) {
+            return Some(stairs_pos);
+        }
+        ALL_DIRECTIONS.iter().map(|&dir| stairs_pos.step(dir)).find(|&pos| is_free(pos))
+    }
+
+    pub(super) fn take_stairs(&mut self, e: Entity, down: bool) -> ActionResult {
+        // monsters stay on their own level
+        if e != PLAYER {
+            return Err(ActionError::InvalidActor);
+        }
+        let pos = self.positions.get(&e).cloned().ok_or(ActionError::InvalidActor)?;
+        let (stairs, arrival_stairs, new_depth) = if down {
+            (Tile::StairsDown, Tile::StairsUp, self.depth + 1)
+        } else {
+            (Tile::StairsUp, Tile::StairsDown, self.depth - 1)
+        };
+        if self.tile(pos) != stairs {
+            return Err(ActionError::NoStairs);
+        }
+        // check before touching anything, since failed actions mustn't change the game
+        let arrival = match self.levels.get(&new_depth) {
+            Some(level) => {
+                let arrival = Game::arrival_position(&level.tiles, &level.actors, arrival_stairs);
+                Some(arrival.ok_or(ActionError::Occupied)?)
+            }
+            None => None,
+        };
+
+        let old_depth = self.depth;
+        // a new level can't be checked until it's been made, so remember enough to undo that
+        let (rng, prev_entity) = (self.rng.clone(), self.prev_entity);
+        let old_level = self.stash_level();
+        self.levels.insert(old_depth, old_level);
+        self.depth = new_depth;
+        let arrival = match (self.levels.remove(&new_depth), arrival) {
+            (Some(level), Some(arrival)) => {
+                self.restore_level(level);
+                Some(arrival)
+            }
+            _ => {
+                map::generate_level(self, new_depth);
+                Game::arrival_position(&self.tiles, &self.actors, arrival_stairs)
+            }
+        };
+        match arrival {
+            Some(arrival) if self.set_actor_position(e, arrival).is_ok() => {}
+            // the stairs on a new level can be completely surrounded, in which case it's thrown
+            // away again and nothing happens after all
+            _ => {
+                self.stash_level();
+                self.types.retain(|&e, _| e <= prev_entity);
+                self.rng = rng;
+                self.prev_entity = prev_entity;
+                self.depth = old_depth;
+                if let Some(old_level) = self.levels.remove(&old_depth) {
+                    self.restore_level(old_level);
+                }
+                let _ = self.set_actor_position(e, pos);
+                return Err(ActionError::Occupied);
+            }
+        }
+        self.events.push(GameEvent::ChangedLevel { from: old_depth, to: new_depth });
+        Ok(())
+    }
+}
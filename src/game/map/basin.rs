@@ -0,0 +1,36 @@
+use rand::prelude::*;
+
+use super::{MapBuilder, MapGenerator};
+use crate::game::Tile;
+use crate::game::geometry::Position;
+
+/// A roughly circular clearing with a few trees, and monsters everywhere but the middle.
+pub struct Basin;
+
+impl MapGenerator for Basin {
+    fn generate(&self, map: &mut MapBuilder) {
+        let mut region = Vec::new();
+        for x in -17..=17 {
+            for y in -17..=17 {
+                let sq = (x*x + y*y) as u32;
+                if sq <= 25 || sq < 18*18 && !map.rng().gen_ratio(sq, 20*20) {
+                    let pos = Position { x, y };
+                    if sq <= 2 || map.rng().gen_ratio(14, 15) {
+                        map.set_tile(pos, Tile::Ground);
+                        region.push(pos);
+                    } else {
+                        map.set_tile(pos, Tile::Tree);
+                    }
+                }
+            }
+        }
+        // a pond somewhere away from the middle
+        let (angle, dist) = (map.rng().gen_range(0.0, std::f64::consts::TAU), map.rng().gen_range(8.0, 13.0));
+        let center = Position { x: (angle.cos() * dist) as i32, y: (angle.sin() * dist) as i32 };
+        let radius = map.rng().gen_range(2, 5);
+        map.add_pond(center, radius);
+        map.place_vaults(1);
+        let table = map.spawn_table().clone();
+        map.populate(&region, &table);
+    }
+}
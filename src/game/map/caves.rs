@@ -0,0 +1,64 @@
+use rand::prelude::*;
+
+use super::{MapBuilder, MapGenerator};
+use crate::game::Tile;
+use crate::game::geometry::{ALL_DIRECTIONS, Position};
+
+const HALF_WIDTH: i32 = 24;
+const HALF_HEIGHT: i32 = 16;
+const SMOOTHING_PASSES: usize = 4;
+const POOLS: usize = 3;
+
+/// Twisty caverns grown with a cellular automaton.
+pub struct Caves;
+
+fn in_bounds(pos: Position) -> bool {
+    pos.x.abs() < HALF_WIDTH && pos.y.abs() < HALF_HEIGHT
+}
+
+fn positions() -> impl Iterator<Item = Position> {
+    (-HALF_WIDTH..=HALF_WIDTH).flat_map(|x| (-HALF_HEIGHT..=HALF_HEIGHT).map(move |y| Position { x, y }))
+}
+
+impl MapGenerator for Caves {
+    fn generate(&self, map: &mut MapBuilder) {
+        for pos in positions() {
+            let wall = !in_bounds(pos) || map.rng().gen_ratio(45, 100);
+            map.set_tile(pos, if wall { Tile::Wall } else { Tile::Ground });
+        }
+        for _ in 0..SMOOTHING_PASSES {
+            // a wall stays a wall with four walls around it, and open ground needs five to fill in
+            let walls: Vec<_> = positions().filter(|&pos| {
+                let neighbors = ALL_DIRECTIONS.iter()
+                    .filter(|&&dir| map.tile(pos.step(dir)).unwrap_or(Tile::Wall) == Tile::Wall)
+                    .count();
+                !in_bounds(pos) || neighbors >= 5 || neighbors == 4 && map.tile(pos) == Some(Tile::Wall)
+            }).collect();
+            for pos in positions() {
+                map.set_tile(pos, Tile::Ground);
+            }
+            for pos in walls {
+                map.set_tile(pos, Tile::Wall);
+            }
+        }
+        for x in -1..=1 {
+            for y in -1..=1 {
+                map.set_tile(Position { x, y }, Tile::Ground);
+            }
+        }
+        // underground pools collect in some of the open spaces
+        let open: Vec<_> = positions()
+            .filter(|&pos| map.tile(pos) == Some(Tile::Ground) && pos.chebyshev_distance(Position { x: 0, y: 0 }) > 5)
+            .collect();
+        for _ in 0..POOLS {
+            if let Some(&center) = open.choose(map.rng()) {
+                let radius = map.rng().gen_range(1, 4);
+                map.add_pond(center, radius);
+            }
+        }
+        let region: Vec<_> = positions().filter(|&pos| map.tile(pos) == Some(Tile::Ground)).collect();
+        map.place_vaults(1);
+        let table = map.spawn_table().clone();
+        map.populate(&region, &table);
+    }
+}
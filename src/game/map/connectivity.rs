@@ -0,0 +1,156 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::game::{Game, Tile};
+use crate::game::geometry::{ORTHOGONAL_DIRECTIONS, Position};
+
+// pockets smaller than this are filled in rather than dug out
+const MIN_REGION_SIZE: usize = 6;
+
+fn is_open(tiles: &HashMap<Position, Tile>, pos: Position) -> bool {
+    tiles.get(&pos).is_some_and(|tile| tile.walkable())
+}
+
+/// Walkable tiles reachable from a starting point with orthogonal steps, so that crabs can get
+/// everywhere the player can short of closed doors, which they can't open.
+pub(super) fn flood_fill(tiles: &HashMap<Position, Tile>, start: Position) -> HashSet<Position> {
+    let mut seen = HashSet::new();
+    if !is_open(tiles, start) {
+        return seen;
+    }
+    let mut queue = VecDeque::new();
+    seen.insert(start);
+    queue.push_back(start);
+    while let Some(pos) = queue.pop_front() {
+        for &dir in &ORTHOGONAL_DIRECTIONS {
+            let next = pos.step(dir);
+            if is_open(tiles, next) && seen.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    seen
+}
+
+/// Makes every walkable tile reachable from the origin. Small pockets are filled with whatever
+/// surrounds them, and bigger ones get a tunnel dug to the nearest reachable tile, going
+/// around any vaults.
+pub(super) fn connect(g: &mut Game, vault_tiles: &HashSet<Position>) {
+    let origin = Position { x: 0, y: 0 };
+    // the player arrives here, so everything else gets connected to it
+    if !is_open(&g.tiles, origin) {
+        g.tiles.insert(origin, Tile::Ground);
+    }
+    loop {
+        let reachable = flood_fill(&g.tiles, origin);
+        // smallest position first, so the repairs don't depend on hash order
+        let stray = g.tiles.keys().cloned()
+            .filter(|&pos| is_open(&g.tiles, pos) && !reachable.contains(&pos))
+            .min();
+        let stray = match stray {
+            Some(pos) => pos,
+            None => { return; }
+        };
+        let region = flood_fill(&g.tiles, stray);
+        let occupied = region.iter().any(|pos| g.actors.contains_key(pos));
+        if region.len() < MIN_REGION_SIZE && !occupied {
+            fill(g, &region);
+        } else {
+            dig_tunnel(g, &region, &reachable, vault_tiles);
+        }
+    }
+}
+
+fn fill(g: &mut Game, region: &HashSet<Position>) {
+    let mut trees = 0;
+    let mut walls = 0;
+    for &pos in region {
+        for &dir in &ORTHOGONAL_DIRECTIONS {
+            match g.tiles.get(&pos.step(dir)) {
+                Some(Tile::Tree) => trees += 1,
+                Some(tile) if tile.walkable() => {}
+                _ => walls += 1,
+            }
+        }
+    }
+    let filler = if trees > walls { Tile::Tree } else { Tile::Wall };
+    for &pos in region {
+        g.tiles.insert(pos, filler);
+    }
+}
+
+/// Clears the shortest orthogonal path from the region to somewhere already reachable,
+/// without leaving the area the map covers.
+fn dig_tunnel(g: &mut Game, region: &HashSet<Position>, reachable: &HashSet<Position>,
+              vault_tiles: &HashSet<Position>) {
+    let (min_x, max_x, min_y, max_y) = g.tiles.keys().fold(
+        (i32::MAX, i32::MIN, i32::MAX, i32::MIN),
+        |(min_x, max_x, min_y, max_y), pos| {
+            (min_x.min(pos.x), max_x.max(pos.x), min_y.min(pos.y), max_y.max(pos.y))
+        });
+    let in_bounds = |pos: Position| min_x <= pos.x && pos.x <= max_x && min_y <= pos.y && pos.y <= max_y;
+
+    let mut starts: Vec<_> = region.iter().cloned().collect();
+    starts.sort();
+    let mut came_from = HashMap::new();
+    let mut queue = VecDeque::new();
+    for pos in starts {
+        came_from.insert(pos, pos);
+        queue.push_back(pos);
+    }
+    while let Some(pos) = queue.pop_front() {
+        for &dir in &ORTHOGONAL_DIRECTIONS {
+            let next = pos.step(dir);
+            if !in_bounds(next) || came_from.contains_key(&next)
+                || vault_tiles.contains(&next) && !reachable.contains(&next) {
+                continue;
+            }
+            came_from.insert(next, pos);
+            if reachable.contains(&next) {
+                let mut pos = pos;
+                while !region.contains(&pos) {
+                    g.tiles.insert(pos, Tile::Ground);
+                    pos = came_from[&pos];
+                }
+                return;
+            }
+            queue.push_back(next);
+        }
+    }
+    // there's no way through, so it's not worth keeping
+    fill(g, region);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::map::{generate_level, MapKind};
+
+    fn assert_connected(g: &Game, what: &str) {
+        let reachable = flood_fill(&g.tiles, Position { x: 0, y: 0 });
+        for (&pos, &tile) in &g.tiles {
+            if tile.walkable() {
+                assert!(reachable.contains(&pos), "{}: {:?} at {:?} is cut off", what, tile, pos);
+            }
+        }
+        for &pos in g.actors.keys() {
+            assert!(reachable.contains(&pos), "{}: actor at {:?} is cut off", what, pos);
+        }
+        assert!(g.tiles.values().any(|&tile| tile == Tile::StairsDown), "{}: no stairs down", what);
+    }
+
+    #[test]
+    fn generated_maps_are_connected() {
+        for &kind in &MapKind::ALL {
+            for seed in 0..50 {
+                let mut g = Game::new_with_map(seed, kind);
+                assert_connected(&g, &format!("{} seed {} depth 1", kind.name(), seed));
+                for depth in 2..=4 {
+                    g.tiles.clear();
+                    g.actors.clear();
+                    generate_level(&mut g, depth);
+                    assert_connected(&g, &format!("{} seed {} depth {}", kind.name(), seed, depth));
+                }
+            }
+        }
+    }
+}
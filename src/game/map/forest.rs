@@ -0,0 +1,54 @@
+use rand::prelude::*;
+
+use super::{MapBuilder, MapGenerator};
+use crate::game::Tile;
+use crate::game::geometry::Position;
+
+const RADIUS: i32 = 20;
+const CLEARINGS: usize = 6;
+
+/// Thick woods which are hard to see far through, with the odd clearing.
+pub struct Forest;
+
+impl MapGenerator for Forest {
+    fn generate(&self, map: &mut MapBuilder) {
+        let origin = Position { x: 0, y: 0 };
+        let mut clearings = vec![(origin, 2)];
+        for _ in 0..CLEARINGS {
+            let center = Position {
+                x: map.rng().gen_range(-RADIUS + 4, RADIUS - 3),
+                y: map.rng().gen_range(-RADIUS + 4, RADIUS - 3),
+            };
+            clearings.push((center, map.rng().gen_range(2, 5)));
+        }
+        let in_clearing = |pos: Position| clearings.iter().any(|&(center, radius)| {
+            let (dx, dy) = (pos.x - center.x, pos.y - center.y);
+            dx * dx + dy * dy <= radius * radius
+        });
+        let mut region = Vec::new();
+        for x in -RADIUS..=RADIUS {
+            for y in -RADIUS..=RADIUS {
+                let sq = x * x + y * y;
+                if sq > RADIUS * RADIUS {
+                    continue;
+                }
+                let pos = Position { x, y };
+                // the edge of the woods is too dense to get through
+                let tree = sq > (RADIUS - 2) * (RADIUS - 2)
+                    || !in_clearing(pos) && map.rng().gen_ratio(2, 5);
+                if tree {
+                    map.set_tile(pos, Tile::Tree);
+                } else {
+                    map.set_tile(pos, Tile::Ground);
+                    region.push(pos);
+                }
+            }
+        }
+        // a pond in one of the clearings
+        let &(center, radius) = clearings[1..].choose(map.rng()).unwrap();
+        map.add_pond(center, radius - 1);
+        map.place_vaults(2);
+        let table = map.spawn_table().clone();
+        map.populate(&region, &table);
+    }
+}
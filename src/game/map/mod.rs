@@ -0,0 +1,268 @@
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashSet, VecDeque};
+
+use super::{ActorType, Game, Tile};
+use super::actor::{ActorState, Home};
+use super::geometry::{ALL_DIRECTIONS, ORTHOGONAL_DIRECTIONS, Position};
+
+mod basin;
+mod caves;
+mod connectivity;
+mod forest;
+mod ruins;
+mod spawn;
+mod vault;
+
+use basin::Basin;
+use caves::Caves;
+use forest::Forest;
+use ruins::Ruins;
+pub use spawn::{SpawnEntry, SpawnTable, SpawnTableError};
+pub use vault::Vault;
+
+const VAULT_ATTEMPTS: usize = 20;
+
+pub(super) trait MapGenerator {
+    /// Lays out the terrain and monsters for one level. The player arrives at the origin, so it
+    /// has to be left as open ground; stairs get added afterwards.
+    fn generate(&self, map: &mut MapBuilder);
+}
+
+/// The built in generators. Games remember which one they use so that deeper levels, saves
+/// and replays all come out the same.
+#[derive(Debug, Copy, Clone, Default, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum MapKind {
+    #[default]
+    Basin,
+    Caves,
+    Ruins,
+    Forest,
+}
+
+impl MapKind {
+    pub const ALL: [MapKind; 4] = [MapKind::Basin, MapKind::Caves, MapKind::Ruins, MapKind::Forest];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MapKind::Basin => "basin",
+            MapKind::Caves => "caves",
+            MapKind::Ruins => "ruins",
+            MapKind::Forest => "forest",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MapKind> {
+        MapKind::ALL.iter().cloned().find(|kind| kind.name() == name)
+    }
+
+    fn generator(self) -> &'static dyn MapGenerator {
+        match self {
+            MapKind::Basin => &Basin,
+            MapKind::Caves => &Caves,
+            MapKind::Ruins => &Ruins,
+            MapKind::Forest => &Forest,
+        }
+    }
+}
+
+/// What a generator gets to work with: the level's terrain, the game's rng and a way to put
+/// monsters in.
+pub struct MapBuilder<'a> {
+    game: &'a mut Game,
+    depth: u32,
+    spawn_table: SpawnTable,
+    populations: Vec<(Vec<Position>, SpawnTable)>,
+    vault_tiles: HashSet<Position>,
+}
+
+impl<'a> MapBuilder<'a> {
+    pub fn rng(&mut self) -> &mut impl Rng {
+        &mut self.game.rng
+    }
+
+    pub fn tile(&self, pos: Position) -> Option<Tile> {
+        self.game.tiles.get(&pos).cloned()
+    }
+
+    pub fn set_tile(&mut self, pos: Position, tile: Tile) {
+        self.game.tiles.insert(pos, tile);
+    }
+
+    /// Returns whether there was room for it.
+    pub fn spawn(&mut self, actor: ActorType, pos: Position) -> bool {
+        self.game.spawn_actor(actor, pos).is_ok()
+    }
+
+    /// The usual table for this depth, for generators which don't need anything special.
+    pub fn spawn_table(&self) -> &SpawnTable {
+        &self.spawn_table
+    }
+
+    /// Floods the ground around a point, deep in the middle and shallow around the edge.
+    pub fn add_pond(&mut self, center: Position, radius: i32) {
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                let pos = Position { x: center.x + x, y: center.y + y };
+                let sq = x * x + y * y;
+                if sq > radius * radius || self.tile(pos) != Some(Tile::Ground) {
+                    continue;
+                }
+                let deep = sq < (radius - 1) * (radius - 1);
+                self.set_tile(pos, if deep { Tile::DeepWater } else { Tile::ShallowWater });
+            }
+        }
+    }
+
+    /// Stamps a vault somewhere random, turned and flipped at random, making sure it doesn't
+    /// cut anything off. Returns whether a spot was found.
+    pub fn place_vault(&mut self, vault: &Vault) -> bool {
+        let origin = Position { x: 0, y: 0 };
+        let reachable = connectivity::flood_fill(&self.game.tiles, origin);
+        let mut anchors: Vec<_> = reachable.iter().cloned().collect();
+        anchors.sort();
+        for _ in 0..VAULT_ATTEMPTS {
+            let anchor = match anchors.choose(&mut self.game.rng) {
+                Some(&pos) => pos,
+                None => { return false; }
+            };
+            let facing = *ORTHOGONAL_DIRECTIONS.choose(&mut self.game.rng).unwrap();
+            let mirrored = self.game.rng.gen();
+            let cells: Vec<_> = vault.cells(facing, mirrored)
+                .map(|(pos, tile, spawn)| (Position { x: anchor.x + pos.x, y: anchor.y + pos.y }, tile, spawn))
+                .collect();
+            // it has to be all on the map, clear of the start and anything already there
+            if !cells.iter().all(|&(pos, _, _)| {
+                self.tile(pos).is_some() && pos.chebyshev_distance(origin) > 2
+                    && !self.vault_tiles.contains(&pos) && !self.game.actors.contains_key(&pos)
+            }) {
+                continue;
+            }
+            let old_tiles: Vec<_> = cells.iter().map(|&(pos, tile, _)| (pos, self.game.tiles.insert(pos, tile))).collect();
+            let now_reachable = connectivity::flood_fill(&self.game.tiles, origin);
+            let in_vault: HashSet<_> = cells.iter().map(|&(pos, _, _)| pos).collect();
+            let connected = reachable.iter().all(|pos| in_vault.contains(pos) || now_reachable.contains(pos))
+                && cells.iter().all(|&(pos, tile, _)| !tile.walkable() || now_reachable.contains(&pos));
+            if !connected {
+                for (pos, tile) in old_tiles {
+                    if let Some(tile) = tile {
+                        self.game.tiles.insert(pos, tile);
+                    }
+                }
+                continue;
+            }
+            for &(pos, _, spawn) in &cells {
+                if let Some(spawn) = spawn {
+                    match self.game.spawn_actor(spawn.actor, pos) {
+                        Ok(e) if spawn.guard => {
+                            self.game.states.insert(e, ActorState::Guard);
+                            self.game.homes.insert(e, Home { pos, idle: ActorState::Guard });
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            self.vault_tiles.extend(in_vault);
+            return true;
+        }
+        false
+    }
+
+    /// Places up to this many vaults picked from the built in ones which suit the level.
+    pub fn place_vaults(&mut self, count: usize) {
+        let (map_kind, depth) = (self.game.map_kind, self.depth);
+        let vaults: Vec<_> = Vault::builtin().iter().filter(|vault| vault.fits(map_kind, depth)).collect();
+        for _ in 0..count {
+            if let Some(vault) = vaults.choose(&mut self.game.rng) {
+                self.place_vault(vault);
+            }
+        }
+    }
+
+    // monsters only start out where the player can get to them
+    fn is_free(&self, pos: Position, actor: ActorType) -> bool {
+        self.tile(pos).is_some_and(|tile| tile.walkable() && tile.passable_for(actor))
+            && !self.game.actors.contains_key(&pos)
+    }
+
+    /// Fills a region with monsters from a table. Groups spread out from a random starting
+    /// tile, staying inside the region but out of any vaults. This happens once the terrain is
+    /// finished and connected up, so nothing gets sealed away.
+    pub fn populate(&mut self, region: &[Position], table: &SpawnTable) {
+        self.populations.push((region.to_vec(), table.clone()));
+    }
+
+    fn spawn_population(&mut self, mut region: Vec<Position>, table: &SpawnTable) {
+        let origin = Position { x: 0, y: 0 };
+        region.retain(|pos| !self.vault_tiles.contains(pos));
+        region.sort();
+        region.dedup();
+        let in_region: HashSet<_> = region.iter().cloned().collect();
+        for (idx, size) in table.choose_groups(&mut self.game.rng, region.len()) {
+            let entry = table.entries()[idx];
+            let far_enough = |pos: Position| pos.chebyshev_distance(origin) >= entry.min_distance;
+            let starts: Vec<_> = region.iter().cloned()
+                .filter(|&pos| far_enough(pos) && self.is_free(pos, entry.actor))
+                .collect();
+            let start = match starts.choose(&mut self.game.rng) {
+                Some(&pos) => pos,
+                None => { continue; }
+            };
+            let mut seen = HashSet::new();
+            let mut queue = VecDeque::new();
+            seen.insert(start);
+            queue.push_back(start);
+            let mut spawned = 0;
+            while let Some(pos) = queue.pop_front() {
+                if spawned == size {
+                    break;
+                }
+                if far_enough(pos) && self.is_free(pos, entry.actor) && self.spawn(entry.actor, pos) {
+                    spawned += 1;
+                }
+                for &dir in &ALL_DIRECTIONS {
+                    let next = pos.step(dir);
+                    if in_region.contains(&next) && seen.insert(next) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub(super) fn generate_level(g: &mut Game, depth: u32) {
+    let generator = g.map_kind.generator();
+    let mut map = MapBuilder {
+        game: g,
+        depth,
+        spawn_table: SpawnTable::for_depth(depth),
+        populations: Vec::new(),
+        vault_tiles: HashSet::new(),
+    };
+    generator.generate(&mut map);
+    connectivity::connect(map.game, &map.vault_tiles);
+    for (region, table) in std::mem::take(&mut map.populations) {
+        map.spawn_population(region, &table);
+    }
+    place_stairs(g, depth);
+}
+
+/// Stairs down go somewhere far from the middle, where you arrive from above.
+fn place_stairs(g: &mut Game, depth: u32) {
+    let origin = Position { x: 0, y: 0 };
+    if depth > 1 {
+        g.tiles.insert(origin, Tile::StairsUp);
+    }
+    let mut candidates: Vec<_> = g.tiles.iter()
+        .filter(|&(pos, &tile)| tile == Tile::Ground && !g.actors.contains_key(pos))
+        .map(|(&pos, _)| pos)
+        .collect();
+    // sorted so the choice only depends on the rng
+    candidates.sort_by_key(|pos| (-(pos.x * pos.x + pos.y * pos.y), *pos));
+    candidates.truncate((candidates.len() / 4).max(1));
+    if let Some(&pos) = candidates.choose(&mut g.rng) {
+        g.tiles.insert(pos, Tile::StairsDown);
+    }
+}
@@ -0,0 +1,125 @@
+use rand::prelude::*;
+
+use super::{MapBuilder, MapGenerator};
+use crate::game::Tile;
+use crate::game::geometry::{Direction, Position};
+
+const HALF_WIDTH: i32 = 30;
+const HALF_HEIGHT: i32 = 20;
+const ROOM_ATTEMPTS: usize = 40;
+
+/// Crumbling rooms joined up by corridors.
+pub struct Ruins;
+
+#[derive(Copy, Clone)]
+struct Room {
+    min: Position,
+    max: Position,
+}
+
+impl Room {
+    fn center(self) -> Position {
+        Position { x: (self.min.x + self.max.x) / 2, y: (self.min.y + self.max.y) / 2 }
+    }
+
+    // rooms need at least a wall between them
+    fn overlaps(self, other: Room) -> bool {
+        self.min.x <= other.max.x + 1 && other.min.x <= self.max.x + 1
+            && self.min.y <= other.max.y + 1 && other.min.y <= self.max.y + 1
+    }
+
+    /// The ring of tiles just outside the room.
+    fn border(self) -> impl Iterator<Item = Position> {
+        let outer = Room {
+            min: Position { x: self.min.x - 1, y: self.min.y - 1 },
+            max: Position { x: self.max.x + 1, y: self.max.y + 1 },
+        };
+        outer.positions().filter(move |pos| {
+            pos.x == outer.min.x || pos.x == outer.max.x || pos.y == outer.min.y || pos.y == outer.max.y
+        })
+    }
+
+    fn positions(self) -> impl Iterator<Item = Position> {
+        (self.min.x..=self.max.x).flat_map(move |x| (self.min.y..=self.max.y).map(move |y| Position { x, y }))
+    }
+}
+
+fn random_room(rng: &mut impl Rng, center: Option<Position>) -> Room {
+    let (w, h) = (rng.gen_range(4, 11), rng.gen_range(3, 8));
+    let center = center.unwrap_or_else(|| Position {
+        x: rng.gen_range(-HALF_WIDTH + w / 2 + 1, HALF_WIDTH - w / 2 - 1),
+        y: rng.gen_range(-HALF_HEIGHT + h / 2 + 1, HALF_HEIGHT - h / 2 - 1),
+    });
+    let min = Position { x: center.x - w / 2, y: center.y - h / 2 };
+    Room { min, max: Position { x: min.x + w - 1, y: min.y + h - 1 } }
+}
+
+impl MapGenerator for Ruins {
+    fn generate(&self, map: &mut MapBuilder) {
+        for x in -HALF_WIDTH..=HALF_WIDTH {
+            for y in -HALF_HEIGHT..=HALF_HEIGHT {
+                map.set_tile(Position { x, y }, Tile::Wall);
+            }
+        }
+        // the first room is where the player shows up
+        let mut rooms = vec![random_room(map.rng(), Some(Position { x: 0, y: 0 }))];
+        for _ in 0..ROOM_ATTEMPTS {
+            let room = random_room(map.rng(), None);
+            if rooms.iter().all(|&other| !room.overlaps(other)) {
+                rooms.push(room);
+            }
+        }
+        for &room in &rooms {
+            for pos in room.positions() {
+                map.set_tile(pos, Tile::Ground);
+            }
+        }
+        // joining each room to the previous one keeps everything connected
+        for pair in rooms.windows(2) {
+            let (from, to) = (pair[0].center(), pair[1].center());
+            let corner = if map.rng().gen() {
+                Position { x: to.x, y: from.y }
+            } else {
+                Position { x: from.x, y: to.y }
+            };
+            for &(a, b) in &[(from, corner), (corner, to)] {
+                for x in a.x.min(b.x)..=a.x.max(b.x) {
+                    for y in a.y.min(b.y)..=a.y.max(b.y) {
+                        map.set_tile(Position { x, y }, Tile::Ground);
+                    }
+                }
+            }
+        }
+        // doorways are where a corridor passes through a room's wall
+        for &room in &rooms {
+            for pos in room.border() {
+                let walls = |a: Direction, b: Direction| {
+                    map.tile(pos.step(a)) == Some(Tile::Wall) && map.tile(pos.step(b)) == Some(Tile::Wall)
+                };
+                let doorway = map.tile(pos) == Some(Tile::Ground)
+                    && (walls(Direction::North, Direction::South) || walls(Direction::East, Direction::West));
+                if doorway && map.rng().gen_ratio(1, 2) {
+                    let door = if map.rng().gen_ratio(2, 3) { Tile::ClosedDoor } else { Tile::OpenDoor };
+                    map.set_tile(pos, door);
+                }
+            }
+        }
+        // trees have taken root in some of the old rooms, and the ceiling has come down in others
+        let mut region = Vec::new();
+        for &room in &rooms[1..] {
+            for pos in room.positions() {
+                if map.rng().gen_ratio(1, 20) {
+                    map.set_tile(pos, Tile::Tree);
+                } else if map.rng().gen_ratio(1, 20) {
+                    map.set_tile(pos, Tile::Rubble);
+                } else {
+                    region.push(pos);
+                }
+            }
+        }
+        map.place_vaults(2);
+        // monsters keep to the rooms, and stay out of the one the player starts in
+        let table = map.spawn_table().clone();
+        map.populate(&region, &table);
+    }
+}
@@ -0,0 +1,129 @@
+use rand::prelude::*;
+
+use crate::game::ActorType;
+use crate::game::rng::GameRng;
+
+// past this depth levels stop getting any harder
+const MAX_DIFFICULTY: u32 = 5;
+
+const DEFAULT_TILES_PER_GROUP: u32 = 9;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SpawnEntry {
+    pub actor: ActorType,
+    pub weight: u32,
+    pub group_size: (u32, u32),
+    pub min_count: u32,
+    pub max_count: Option<u32>,
+    /// Groups never start closer than this to the player's starting point.
+    pub min_distance: i32,
+}
+
+/// Which monsters turn up in a region, and how many.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SpawnTable {
+    entries: Vec<SpawnEntry>,
+    tiles_per_group: u32,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SpawnTableError {
+    /// Groups have to have at least one monster, and no more than the largest size.
+    BadGroupSize(ActorType),
+    MinCountOverMaxCount(ActorType),
+}
+
+impl SpawnTable {
+    pub fn new(entries: Vec<SpawnEntry>, tiles_per_group: u32) -> Result<SpawnTable, SpawnTableError> {
+        for entry in &entries {
+            let (min, max) = entry.group_size;
+            if min == 0 || min > max {
+                return Err(SpawnTableError::BadGroupSize(entry.actor));
+            }
+            if entry.max_count.is_some_and(|max_count| entry.min_count > max_count) {
+                return Err(SpawnTableError::MinCountOverMaxCount(entry.actor));
+            }
+        }
+        Ok(SpawnTable { entries, tiles_per_group })
+    }
+
+    /// The table built from the creature definitions.
+    pub fn for_depth(depth: u32) -> SpawnTable {
+        let d = depth.clamp(1, MAX_DIFFICULTY);
+        let entries = ActorType::all().filter_map(|actor| {
+            let spawn = actor.def().spawn?;
+            Some(SpawnEntry {
+                actor,
+                weight: if spawn.more_common_deeper { spawn.weight * d } else { spawn.weight },
+                group_size: spawn.group_size,
+                min_count: spawn.min_count,
+                max_count: spawn.max_count,
+                min_distance: spawn.min_distance,
+            })
+        }).collect();
+        SpawnTable::new(entries, DEFAULT_TILES_PER_GROUP).expect("creature spawn rules are checked when loaded")
+    }
+
+    pub fn entries(&self) -> &[SpawnEntry] {
+        &self.entries
+    }
+
+    /// One group is spawned for this many tiles in the region, on top of the minimum counts.
+    pub fn tiles_per_group(&self) -> u32 {
+        self.tiles_per_group
+    }
+
+    /// Picks the groups to spawn in a region of the given size, as entry indexes and group sizes.
+    pub fn choose_groups(&self, rng: &mut impl Rng, region_size: usize) -> Vec<(usize, u32)> {
+        let mut counts = vec![0; self.entries.len()];
+        let mut chosen = Vec::new();
+        for (idx, entry) in self.entries.iter().enumerate() {
+            for _ in 0..entry.min_count {
+                chosen.push(idx);
+                counts[idx] += 1;
+            }
+        }
+        let extra_groups = region_size / self.tiles_per_group.max(1) as usize;
+        for _ in 0..extra_groups {
+            let available: Vec<_> = self.entries.iter().enumerate()
+                .filter(|&(idx, entry)| entry.max_count.map_or(true, |max| counts[idx] < max))
+                .collect();
+            let total_weight: u32 = available.iter().map(|(_, entry)| entry.weight).sum();
+            if total_weight == 0 {
+                break;
+            }
+            let mut roll = rng.gen_range(0, total_weight);
+            for &(idx, entry) in &available {
+                if roll < entry.weight {
+                    chosen.push(idx);
+                    counts[idx] += 1;
+                    break;
+                }
+                roll -= entry.weight;
+            }
+        }
+        chosen.into_iter().map(|idx| {
+            let (min, max) = self.entries[idx].group_size;
+            (idx, rng.gen_range(min, max + 1))
+        }).collect()
+    }
+
+    /// Average number of each monster picked for regions of the given size. Groups which
+    /// turn out not to fit are still counted, so crowded regions will get fewer than this.
+    ///
+    /// The maximum counts make working this out exactly a pain, so it's estimated from a fixed
+    /// seed instead. That keeps it the same every time, but it's only good to within a few percent.
+    pub fn expected_composition(&self, region_size: usize) -> Vec<(ActorType, f64)> {
+        const TRIALS: u32 = 1000;
+        let mut rng = GameRng::seed_from_u64(0);
+        let mut totals = vec![0; self.entries.len()];
+        for _ in 0..TRIALS {
+            for (idx, size) in self.choose_groups(&mut rng, region_size) {
+                totals[idx] += size;
+            }
+        }
+        self.entries.iter().zip(totals)
+            .map(|(entry, total)| (entry.actor, total as f64 / TRIALS as f64))
+            .collect()
+    }
+}
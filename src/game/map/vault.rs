@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use super::MapKind;
+use crate::game::{ActorType, Tile};
+use crate::game::geometry::{Direction, Position};
+
+const BUILTIN_VAULTS: &str = include_str!("../../../data/vaults.ron");
+
+static VAULTS: OnceLock<Vec<Vault>> = OnceLock::new();
+
+fn default_tile() -> Tile {
+    Tile::Ground
+}
+
+fn default_min_depth() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct LegendEntry {
+    #[serde(default = "default_tile")]
+    tile: Tile,
+    #[serde(default)]
+    spawn: Option<String>,
+    /// The creature stays put instead of doing whatever it usually does.
+    #[serde(default)]
+    guard: bool,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(super) struct VaultSpawn {
+    pub(super) actor: ActorType,
+    pub(super) guard: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct VaultDef {
+    name: String,
+    #[serde(default)]
+    maps: Vec<MapKind>,
+    #[serde(default = "default_min_depth")]
+    min_depth: u32,
+    rows: Vec<String>,
+    #[serde(default)]
+    legend: HashMap<char, LegendEntry>,
+}
+
+/// A hand made piece of map, read from `data/vaults.ron`.
+#[derive(Debug, Clone)]
+pub struct Vault {
+    maps: Vec<MapKind>,
+    min_depth: u32,
+    /// Relative to the middle of the vault, with blank cells left out.
+    cells: Vec<(Position, Tile, Option<VaultSpawn>)>,
+}
+
+impl Vault {
+    fn from_def(def: VaultDef) -> Result<Vault, String> {
+        let height = def.rows.len() as i32;
+        let width = def.rows.iter().map(|row| row.chars().count()).max().unwrap_or(0) as i32;
+        let mut cells = Vec::new();
+        for (y, row) in def.rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let pos = Position { x: x as i32 - width / 2, y: y as i32 - height / 2 };
+                let (tile, spawn) = match (c, def.legend.get(&c)) {
+                    (_, Some(entry)) => {
+                        let spawn = match &entry.spawn {
+                            Some(name) => Some(VaultSpawn {
+                                actor: ActorType::named(name)
+                                    .ok_or_else(|| format!("{} has unknown creature {}", def.name, name))?,
+                                guard: entry.guard,
+                            }),
+                            None => None,
+                        };
+                        (entry.tile, spawn)
+                    }
+                    (' ', None) => { continue; }
+                    ('#', None) => (Tile::Wall, None),
+                    ('T', None) => (Tile::Tree, None),
+                    ('.', None) => (Tile::Ground, None),
+                    ('~', None) => (Tile::ShallowWater, None),
+                    ('=', None) => (Tile::DeepWater, None),
+                    ('+', None) => (Tile::ClosedDoor, None),
+                    ('\'', None) => (Tile::OpenDoor, None),
+                    (':', None) => (Tile::Rubble, None),
+                    (c, None) => { return Err(format!("{} has unknown character {:?}", def.name, c)); }
+                };
+                cells.push((pos, tile, spawn));
+            }
+        }
+        Ok(Vault { maps: def.maps, min_depth: def.min_depth, cells })
+    }
+
+    /// The vaults that come with the game.
+    pub fn builtin() -> &'static [Vault] {
+        VAULTS.get_or_init(|| {
+            let defs: Vec<VaultDef> = ron::de::from_str(BUILTIN_VAULTS).expect("malformed built in vaults");
+            defs.into_iter().map(Vault::from_def).collect::<Result<_, _>>().expect("invalid built in vaults")
+        })
+    }
+
+    pub fn fits(&self, map_kind: MapKind, depth: u32) -> bool {
+        (self.maps.is_empty() || self.maps.contains(&map_kind)) && depth >= self.min_depth
+    }
+
+    /// The cells turned to face a direction, and then maybe mirrored.
+    pub(super) fn cells(&self, facing: Direction, mirrored: bool)
+        -> impl Iterator<Item = (Position, Tile, Option<VaultSpawn>)> + '_
+    {
+        self.cells.iter().map(move |&(pos, tile, spawn)| {
+            let pos = pos.rotate(facing);
+            (if mirrored { pos.mirror() } else { pos }, tile, spawn)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_turn_and_flip() {
+        let def = VaultDef {
+            name: "corner".to_string(),
+            maps: Vec::new(),
+            min_depth: 1,
+            rows: vec!["#T".to_string(), " .".to_string()],
+            legend: HashMap::new(),
+        };
+        let vault = Vault::from_def(def).unwrap();
+        let cells = |facing, mirrored| {
+            let mut cells: Vec<_> = vault.cells(facing, mirrored)
+                .map(|(pos, tile, _)| ((pos.x, pos.y), tile))
+                .collect();
+            cells.sort_by_key(|&(pos, _)| pos);
+            cells
+        };
+        let expected = |wall, tree| {
+            let mut cells = vec![(wall, Tile::Wall), (tree, Tile::Tree), ((0, 0), Tile::Ground)];
+            cells.sort_by_key(|&(pos, _)| pos);
+            cells
+        };
+        assert_eq!(cells(Direction::North, false), expected((-1, -1), (0, -1)));
+        assert_eq!(cells(Direction::East, false), expected((1, -1), (1, 0)));
+        assert_eq!(cells(Direction::South, false), expected((1, 1), (0, 1)));
+        assert_eq!(cells(Direction::West, false), expected((-1, 1), (-1, 0)));
+        assert_eq!(cells(Direction::North, true), expected((1, -1), (0, -1)));
+        assert_eq!(cells(Direction::East, true), expected((-1, -1), (-1, 0)));
+        assert_eq!(cells(Direction::West, true), expected((1, 1), (1, 0)));
+    }
+}
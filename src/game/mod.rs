@@ -2,35 +2,64 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 use std::num::NonZeroU64;
 
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
 mod actor;
-mod fov;
+mod creature;
+mod event;
+mod item;
+mod level;
 mod map;
+mod path;
+mod replay;
+mod rng;
+mod save;
+mod undo;
 
-use actor::ActorState;
+use actor::{ActorState, Home};
 use geometry::{Direction, Position};
+use level::Level;
+use path::DistanceMap;
+use rng::GameRng;
 
+pub mod fov;
 pub mod geometry;
 
-pub use actor::ActorType;
+pub use actor::{AiState, Status, StatusEffect};
+pub use creature::{ActorType, CreatureDef, CreatureError, Idle, Movement, SpawnRules, load_creatures};
+pub use event::GameEvent;
+pub use item::ItemType;
+pub use map::{MapKind, SpawnEntry, SpawnTable, SpawnTableError};
+pub use replay::{Playback, Replay, ReplayError};
+pub use save::LoadError;
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
 struct Entity(NonZeroU64);
 
-// TODO: switch to new(1).unwrap() once that's const
-const PLAYER: Entity = Entity(unsafe { NonZeroU64::new_unchecked(1) });
+const PLAYER: Entity = match NonZeroU64::new(1) {
+    Some(id) => Entity(id),
+    None => unreachable!(),
+};
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum EntityType {
     Actor(ActorType),
     Corpse(ActorType),
+    Item(ItemType),
 }
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Tile {
     Wall,
     Tree,
     Ground,
+    StairsDown,
+    StairsUp,
+    ShallowWater,
+    DeepWater,
+    OpenDoor,
+    ClosedDoor,
+    Rubble,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -43,17 +72,37 @@ pub enum Obstruction {
 impl Tile {
     pub fn obstruction(self) -> Obstruction {
         match self {
-            Tile::Wall => Obstruction::Full,
-            Tile::Tree => Obstruction::Partial,
-            Tile::Ground => Obstruction::None,
+            Tile::Wall | Tile::ClosedDoor => Obstruction::Full,
+            Tile::Tree | Tile::Rubble => Obstruction::Partial,
+            Tile::Ground | Tile::StairsDown | Tile::StairsUp => Obstruction::None,
+            Tile::ShallowWater | Tile::DeepWater | Tile::OpenDoor => Obstruction::None,
         }
     }
+
+    /// Whether an actor can stand here. Closed doors have to be opened first by anything that
+    /// can't pass through them.
+    pub fn passable_for(self, actor: ActorType) -> bool {
+        match self {
+            Tile::Wall | Tile::Tree => false,
+            Tile::Ground | Tile::StairsDown | Tile::StairsUp => true,
+            Tile::ShallowWater | Tile::OpenDoor | Tile::Rubble => true,
+            Tile::DeepWater => actor.def().swims,
+            Tile::ClosedDoor => actor.def().passes_doors,
+        }
+    }
+
+    /// Whether the player can get through, counting doors as open. Levels are built so that
+    /// every walkable tile can be reached.
+    pub fn walkable(self) -> bool {
+        self == Tile::ClosedDoor || self.passable_for(ActorType::PLAYER)
+    }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum TileView {
     Visible {
         actor: Option<ActorType>,
+        wounded: bool,
         object: Option<EntityType>,
         tile: Tile,
     },
@@ -67,35 +116,46 @@ pub enum TileView {
 
 impl TileView {
     pub fn actor(&self) -> Option<ActorType> {
-        match self {
-            &TileView::Visible { actor, .. } => actor,
+        match *self {
+            TileView::Visible { actor, .. } => actor,
             _ => None,
         }
     }
 
     pub fn object(&self) -> Option<EntityType> {
-        match self {
-            &TileView::Visible { object, .. } => object,
-            &TileView::Remembered { object, .. } => object,
+        match *self {
+            TileView::Visible { object, .. } => object,
+            TileView::Remembered { object, .. } => object,
             _ => None,
         }
     }
 
     pub fn tile(&self) -> Option<Tile> {
-        match self {
-            &TileView::Visible { tile, .. } => Some(tile),
-            &TileView::Remembered { tile, .. } => Some(tile),
+        match *self {
+            TileView::Visible { tile, .. } => Some(tile),
+            TileView::Remembered { tile, .. } => Some(tile),
             _ => None,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Action {
     Wait,
     Move(Direction),
     Attack(Direction),
     MoveAttack(Direction),
+    PickUp,
+    // indexes into the player's inventory
+    Drop(usize),
+    Use(usize),
+    Harvest,
+    Descend,
+    Ascend,
+    OpenDoor(Direction),
+    CloseDoor(Direction),
+    /// Starts or stops sneaking, which makes monsters less likely to notice the player.
+    ToggleSneak,
 }
 
 // Include info on what exactly went wrong in error?
@@ -107,10 +167,16 @@ pub enum ActionError {
     Occupied,
     InvalidActor,
     NoValidTarget,
+    NoItem,
+    InvalidItem,
+    InventoryFull,
+    NoStairs,
+    NoDoor,
 }
 
 type ActionResult<Ok = ()> = Result<Ok, ActionError>;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Game {
     tiles: HashMap<Position, Tile>,
 
@@ -122,19 +188,52 @@ pub struct Game {
     actors: HashMap<Position, Entity>,
     objects: HashMap<Position, Vec<Entity>>,
 
-    visible_ghosts: HashSet<Entity>,
+    revealed_actors: HashSet<Entity>,
+    inventory: Vec<Entity>,
 
-    rng: StdRng,
+    health: HashMap<Entity, u32>,
+    energy: HashMap<Entity, u32>,
+    statuses: HashMap<Entity, Vec<StatusEffect>>,
+    // where monsters wander around or guard
+    homes: HashMap<Entity, Home>,
+    sneaking: bool,
+    kills: HashMap<ActorType, u32>,
+
+    rng: GameRng,
     prev_entity: Entity,
     view: HashMap<Position, TileView>,
+
+    // the level the player is on, starting at 1, and all the others they've visited
+    depth: u32,
+    levels: BTreeMap<u32, Level>,
+
+    // everything needed to replay the game from the start
+    seed: u64,
+    map_kind: MapKind,
+    actions: Vec<Action>,
+
+    #[serde(skip)]
+    events: Vec<GameEvent>,
+    // where monsters are heading, worked out afresh every turn
+    #[serde(skip)]
+    distance_maps: HashMap<(ActorType, Position), DistanceMap>,
+
+    practice: bool,
+    used_undo: bool,
+    // the first turn that can be undone, since practice mode might not have been on from the start
+    #[serde(skip)]
+    undo_from: usize,
+    // copies of the game every so often, by turn, for undo to go back to
+    #[serde(skip)]
+    checkpoints: Vec<(usize, Game)>,
 }
 
 impl Game {
     pub fn new(seed: u64) -> Game {
-        Game::new_from_rng(StdRng::seed_from_u64(seed))
+        Game::new_with_map(seed, MapKind::default())
     }
 
-    fn new_from_rng(rng: StdRng) -> Game {
+    pub fn new_with_map(seed: u64, map_kind: MapKind) -> Game {
         let mut g = Game {
             tiles: HashMap::new(),
             types: HashMap::new(),
@@ -142,13 +241,33 @@ impl Game {
             positions: HashMap::new(),
             actors: HashMap::new(),
             objects: HashMap::new(),
-            visible_ghosts: HashSet::new(),
-            rng: rng,
+            revealed_actors: HashSet::new(),
+            inventory: Vec::new(),
+            health: HashMap::new(),
+            energy: HashMap::new(),
+            statuses: HashMap::new(),
+            homes: HashMap::new(),
+            sneaking: false,
+            kills: HashMap::new(),
+            rng: GameRng::seed_from_u64(seed),
             prev_entity: PLAYER,
             view: HashMap::new(),
+            depth: 1,
+            levels: BTreeMap::new(),
+            seed,
+            map_kind,
+            actions: Vec::new(),
+            events: Vec::new(),
+            distance_maps: HashMap::new(),
+            practice: false,
+            used_undo: false,
+            undo_from: 0,
+            checkpoints: Vec::new(),
         };
-        g.types.insert(PLAYER, EntityType::Actor(ActorType::Player));
-        map::generate_basin(&mut g);
+        g.types.insert(PLAYER, EntityType::Actor(ActorType::PLAYER));
+        g.health.insert(PLAYER, ActorType::PLAYER.max_hp());
+        g.energy.insert(PLAYER, actor::ACTION_COST);
+        map::generate_level(&mut g, 1);
         // TODO: handle errors
         let _ = g.set_actor_position(PLAYER, Position { x: 0, y: 0 });
         fov::update_view(&mut g);
@@ -157,19 +276,112 @@ impl Game {
     }
 
     pub fn restart(&mut self) {
-        *self = Game::new_from_rng(self.rng.clone());
+        // pick a fresh seed rather than reusing the rng so the new game can be replayed
+        let practice = self.practice;
+        *self = Game::new_with_map(self.rng.gen(), self.map_kind);
+        self.practice = practice;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn map_kind(&self) -> MapKind {
+        self.map_kind
     }
 
     pub fn view(&self, pos: Position) -> TileView {
         self.view.get(&pos).cloned().unwrap_or(TileView::Unknown)
     }
 
+    /// Every tile the player has seen on this level, as they remember it.
+    pub fn known_tiles(&self) -> impl Iterator<Item = (Position, TileView)> + '_ {
+        self.view.iter()
+            .filter(|&(_, view)| view.tile().is_some())
+            .map(|(&pos, &view)| (pos, view))
+    }
+
     pub fn player_position(&self) -> Option<Position> {
         self.positions.get(&PLAYER).cloned()
     }
 
+    pub fn is_player_alive(&self) -> bool {
+        self.actor_type(PLAYER).is_some()
+    }
+
+    /// Current and maximum hit points, or `None` if the player is dead.
+    pub fn player_health(&self) -> Option<(u32, u32)> {
+        let hp = self.health.get(&PLAYER).cloned()?;
+        Some((hp, self.actor_type(PLAYER)?.max_hp()))
+    }
+
+    /// What every monster on the level is up to, including ones the player can't see. This is
+    /// for debugging.
+    pub fn ai_states(&self) -> Vec<(Position, ActorType, AiState)> {
+        let mut states: Vec<_> = self.states.iter().filter_map(|(e, state)| {
+            Some((*self.positions.get(e)?, self.actor_type(*e)?, state.ai_state()))
+        }).collect();
+        states.sort_by_key(|&(pos, _, _)| pos);
+        states
+    }
+
+    /// Monsters the player can see right now and what they're up to, nearest first.
+    pub fn visible_monsters(&self) -> Vec<(Position, ActorType, AiState)> {
+        let player_pos = match self.player_position() {
+            Some(pos) => pos,
+            None => { return Vec::new(); }
+        };
+        let mut monsters: Vec<_> = self.view.iter().filter_map(|(&pos, view)| match *view {
+            TileView::Visible { actor: Some(actor), .. } if pos != player_pos => {
+                let state = self.actors.get(&pos).and_then(|e| self.states.get(e))?;
+                Some((pos, actor, state.ai_state()))
+            }
+            _ => None,
+        }).collect();
+        monsters.sort_by_key(|&(pos, _, _)| (pos.chebyshev_distance(player_pos), pos));
+        monsters
+    }
+
+    /// Turns the player has taken so far.
+    pub fn turns(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// How many of each kind of creature the player has killed, in the order they're defined.
+    pub fn kills(&self) -> Vec<(ActorType, u32)> {
+        let mut kills: Vec<_> = self.kills.iter().map(|(&actor, &count)| (actor, count)).collect();
+        kills.sort();
+        kills
+    }
+
+    /// How many of the tiles on this level that can be walked on the player has seen, out of
+    /// how many there are.
+    pub fn explored(&self) -> (usize, usize) {
+        let walkable: Vec<_> = self.tiles.iter().filter(|&(_, tile)| tile.walkable()).collect();
+        let seen = walkable.iter().filter(|&&(pos, _)| self.view(*pos).tile().is_some()).count();
+        (seen, walkable.len())
+    }
+
+    pub fn is_sneaking(&self) -> bool {
+        self.sneaking
+    }
+
+    pub fn player_statuses(&self) -> &[StatusEffect] {
+        self.statuses.get(&PLAYER).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Events from the most recent turn which haven't been drained yet.
+    pub fn drain_events(&mut self) -> impl Iterator<Item = GameEvent> + '_ {
+        self.events.drain(..)
+    }
+
     pub fn take_player_action(&mut self, action: Action) -> ActionResult {
+        self.events.clear();
+        if self.practice {
+            self.checkpoint();
+        }
         self.take_action(PLAYER, action)?;
+        self.actions.push(action);
         fov::update_view(self);
         actor::take_actions(self);
         fov::update_view(self);
@@ -177,6 +389,13 @@ impl Game {
         Ok(())
     }
 
+    fn actor_type(&self, e: Entity) -> Option<ActorType> {
+        match self.types.get(&e) {
+            Some(&EntityType::Actor(a)) => Some(a),
+            _ => None,
+        }
+    }
+
     fn tile(&self, pos: Position) -> Tile {
         self.tiles.get(&pos).cloned().unwrap_or(Tile::Wall)
     }
@@ -193,21 +412,11 @@ impl Game {
             Action::Wait => {}
             Action::Move(dir) => {
                 // TODO: ghost should maybe be able to move through walls or something
-                if actor_type == ActorType::Crab && !dir.is_orthogonal() {
+                if actor_type.def().movement == Movement::Orthogonal && !dir.is_orthogonal() {
                     return Err(ActionError::IllegalDiagonal);
                 }
-                if let Some((a, b)) = match dir {
-                    Direction::NorthEast => Some((Direction::North, Direction::East)),
-                    Direction::SouthEast => Some((Direction::South, Direction::East)),
-                    Direction::SouthWest => Some((Direction::South, Direction::West)),
-                    Direction::NorthWest => Some((Direction::North, Direction::West)),
-                    _ => None,
-                } {
-                    if self.tile(pos.step(a)).obstruction() == Obstruction::Full
-                        && self.tile(pos.step(b)).obstruction() == Obstruction::Full
-                    {
-                        return Err(ActionError::IllegalDiagonal);
-                    }
+                if self.squeezed(pos, dir) {
+                    return Err(ActionError::IllegalDiagonal);
                 }
                 self.set_actor_position(e, pos.step(dir))?;
             }
@@ -220,28 +429,38 @@ impl Game {
                         // a bit harder to kill to compensate for being less mobile
                         // I could do beetle style knockback when hitting them diagonally, but I'm
                         // not sure if that's what I really want
-                        if (actor_type == ActorType::Crab || target_type == ActorType::Crab) && !dir.is_orthogonal() {
+                        let orthogonal = |t: ActorType| t.def().movement == Movement::Orthogonal;
+                        if (orthogonal(actor_type) || orthogonal(target_type)) && !dir.is_orthogonal() {
                             return Err(ActionError::IllegalDiagonal);
                         }
-                        if actor_type == ActorType::Player || target_type == ActorType::Player {
+                        if actor_type == ActorType::PLAYER || target_type == ActorType::PLAYER {
                             // TODO: chain push multiple beetles?
-                            if target_type == ActorType::Beetle {
+                            if target_type.def().pushed_on_hit {
+                                let pushed = GameEvent::Pushed {
+                                    actor: target_type,
+                                    from: target_pos,
+                                    to: target_pos.step(dir),
+                                };
                                 match self.take_action(target, Action::Move(dir)) {
                                     Ok(_) => {
-                                        self.states.insert(target, ActorState::Wait);
+                                        self.report_push(pushed);
+                                        self.set_state(target, ActorState::Wait);
                                         return Ok(());
                                     }
                                     Err(ActionError::Occupied) => {
                                         if let Some(&victim) = self.actors.get(&target_pos.step(dir)) {
-                                            // TODO: use force attack action so know what tried to kill victim?
-                                            let _ = self.kill_actor(victim);
-                                            let _ = self.take_action(target, Action::Move(dir));
+                                            // the beetle gets slammed into the victim as hard as
+                                            // it was hit, and both of them take the damage
+                                            self.hit(target, victim, actor_type.damage());
+                                            if self.take_action(target, Action::Move(dir)).is_ok() {
+                                                self.report_push(pushed);
+                                            }
                                         }
                                     }
                                     Err(_) => {}
                                 }
                             }
-                            self.kill_actor(target)?;
+                            self.hit(e, target, actor_type.damage());
                             return Ok(());
                         }
                     }
@@ -252,34 +471,134 @@ impl Game {
                 let result = self.take_action(e, Action::Move(dir));
                 if result == Err(ActionError::Occupied) {
                     self.take_action(e, Action::Attack(dir))?;
+                } else if result == Err(ActionError::Impassible)
+                    && self.tile(pos.step(dir)) == Tile::ClosedDoor
+                    && actor_type.def().opens_doors
+                {
+                    self.take_action(e, Action::OpenDoor(dir))?;
                 } else {
                     result?;
                 }
             }
+            Action::OpenDoor(dir) => {
+                let door = pos.step(dir);
+                if self.tile(door) != Tile::ClosedDoor || !actor_type.def().opens_doors {
+                    return Err(ActionError::NoDoor);
+                }
+                self.tiles.insert(door, Tile::OpenDoor);
+                self.distance_maps.clear();
+                self.events.push(GameEvent::OpenedDoor { actor: actor_type, pos: door });
+            }
+            Action::CloseDoor(dir) => {
+                let door = pos.step(dir);
+                if self.tile(door) != Tile::OpenDoor || !actor_type.def().opens_doors {
+                    return Err(ActionError::NoDoor);
+                }
+                if self.actors.contains_key(&door) || self.objects.get(&door).is_some_and(|o| !o.is_empty()) {
+                    return Err(ActionError::Occupied);
+                }
+                self.tiles.insert(door, Tile::ClosedDoor);
+                self.distance_maps.clear();
+                self.events.push(GameEvent::ClosedDoor { actor: actor_type, pos: door });
+            }
+            Action::PickUp => self.pick_up(e)?,
+            Action::Drop(idx) => self.drop_item(e, idx)?,
+            Action::Use(idx) => self.use_item(e, idx)?,
+            Action::Harvest => self.harvest(e)?,
+            Action::ToggleSneak => {
+                if e != PLAYER {
+                    return Err(ActionError::InvalidActor);
+                }
+                self.sneaking = !self.sneaking;
+                self.events.push(GameEvent::Sneaking { sneaking: self.sneaking });
+            }
+            Action::Descend => self.take_stairs(e, true)?,
+            Action::Ascend => self.take_stairs(e, false)?,
         };
         Ok(())
     }
 
-    fn kill_actor(&mut self, e: Entity) -> ActionResult {
+    /// Reports a push in place of the move it was made with, so it isn't reported twice.
+    fn report_push(&mut self, pushed: GameEvent) {
+        match self.events.last_mut() {
+            Some(last @ GameEvent::Moved { .. }) => *last = pushed,
+            _ => self.events.push(pushed),
+        }
+    }
+
+    fn hit(&mut self, attacker: Entity, target: Entity, damage: u32) {
+        let (attacker_type, target_type) = match (self.actor_type(attacker), self.actor_type(target)) {
+            (Some(a), Some(t)) => (a, t),
+            _ => { return; }
+        };
+        let pos = match self.positions.get(&target) {
+            Some(&pos) => pos,
+            None => { return; }
+        };
+        // armor softens blows but can't make something invulnerable
+        let damage = damage.saturating_sub(target_type.armor()).max(1);
+        if attacker == PLAYER {
+            // fighting is noisy, and whatever got hit knows where the player is now
+            if self.sneaking {
+                self.sneaking = false;
+                self.events.push(GameEvent::Sneaking { sneaking: false });
+            }
+            if let (Some(state), Some(&player_pos)) = (self.states.get(&target), self.positions.get(&PLAYER)) {
+                if state.is_idle() {
+                    self.set_state(target, ActorState::Pursue { target: player_pos, unseen: 0 });
+                }
+            }
+        }
+        self.events.push(GameEvent::Attacked { attacker: attacker_type, target: target_type, pos, damage });
+        let hp = self.health.entry(target).or_insert(0);
+        *hp = hp.saturating_sub(damage);
+        if *hp == 0 {
+            let _ = self.kill_actor(target, Some(attacker));
+        }
+    }
+
+    fn kill_actor(&mut self, e: Entity, killer: Option<Entity>) -> ActionResult {
         // TODO: some type system level stuff to avoid having to revalidate this stuff
         let actor_type = match self.types.get(&e) {
             Some(&EntityType::Actor(a)) => a,
             _ => { return Err(ActionError::InvalidActor); }
         };
+        if killer == Some(PLAYER) {
+            *self.kills.entry(actor_type).or_insert(0) += 1;
+        }
+        let killer = killer.and_then(|k| self.actor_type(k));
         self.states.remove(&e);
+        self.homes.remove(&e);
+        self.energy.remove(&e);
+        self.statuses.remove(&e);
+        self.health.remove(&e);
         self.types.insert(e, EntityType::Corpse(actor_type));
         if let Some(&pos) = self.positions.get(&e) {
+            self.events.push(GameEvent::Killed { victim: actor_type, killer, pos });
             self.actors.remove(&pos);
-            if actor_type != ActorType::Ghost {
-                self.objects.entry(pos).or_insert_with(Vec::new).push(e);
+            if actor_type.def().leaves_corpse {
+                self.objects.entry(pos).or_default().push(e);
             }
-            if actor_type == ActorType::BigJelly {
+            if let Some(item) = actor_type.def().loot {
+                self.place_item(item, pos);
+                self.events.push(GameEvent::Dropped { actor: actor_type, item, pos });
+            }
+            if let Some(split) = actor_type.splits_into() {
+                self.events.push(GameEvent::Split { actor: actor_type, pos });
                 for &dir in &geometry::ORTHOGONAL_DIRECTIONS {
-                    let _ = self.spawn_actor(ActorType::LittleJelly, pos.step(dir));
+                    let _ = self.spawn_actor(split, pos.step(dir));
                 }
             }
         }
-        return Ok(());
+        Ok(())
+    }
+
+    fn add_status(&mut self, e: Entity, status: Status, turns: u32) {
+        let effects = self.statuses.entry(e).or_default();
+        match effects.iter_mut().find(|effect| effect.status == status) {
+            Some(effect) => { effect.turns = effect.turns.max(turns); }
+            None => { effects.push(StatusEffect { status, turns }); }
+        }
     }
 
     fn new_entity(&mut self, entity_type: EntityType) -> Entity {
@@ -295,8 +614,11 @@ impl Game {
     }
 
     fn set_actor_position(&mut self, e: Entity, pos: Position) -> ActionResult<Option<Position>> {
-        let new_tile = self.tile(pos);
-        if new_tile.obstruction() != Obstruction::None {
+        let passable = match self.actor_type(e) {
+            Some(actor) => self.tile(pos).passable_for(actor),
+            None => false,
+        };
+        if !passable {
             return Err(ActionError::Impassible);
         }
 
@@ -311,6 +633,9 @@ impl Game {
         let old_pos = self.positions.insert(e, pos);
         if let Some(old_pos) = old_pos {
             self.actors.remove(&old_pos);
+            if let Some(actor) = self.actor_type(e) {
+                self.events.push(GameEvent::Moved { actor, from: old_pos, to: pos });
+            }
         }
         self.actors.insert(pos, e);
         Ok(old_pos)
@@ -320,9 +645,29 @@ impl Game {
         let e = self.new_entity(EntityType::Actor(t));
         // TODO: don't leak entity on invalid placement
         self.set_actor_position(e, pos)?;
-        if t != ActorType::Player {
-            self.states.insert(e, ActorState::Wait);
+        self.health.insert(e, t.max_hp());
+        if t != ActorType::PLAYER {
+            let idle = ActorState::idle(t);
+            self.states.insert(e, idle);
+            self.homes.insert(e, Home { pos, idle });
         }
         Ok(e)
     }
 }
+
+/// Plays the given number of turns for tests, picking actions at random. Actions that fail don't
+/// count, and play stops early if the player dies.
+#[cfg(test)]
+fn play_randomly(g: &mut Game, rng: &mut GameRng, turns: usize) {
+    let mut played = 0;
+    while played < turns && g.is_player_alive() {
+        let action = match rng.gen_range(0, 10) {
+            0 => Action::Wait,
+            1 => Action::PickUp,
+            _ => Action::MoveAttack(geometry::ALL_DIRECTIONS[rng.gen_range(0, 8)]),
+        };
+        if g.take_player_action(action).is_ok() {
+            played += 1;
+        }
+    }
+}
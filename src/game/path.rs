@@ -0,0 +1,248 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use rand::prelude::*;
+
+use super::{ActorType, Game, Movement, Obstruction, Tile, TileView};
+use super::geometry::{ALL_DIRECTIONS, Direction, ORTHOGONAL_DIRECTIONS, Position};
+
+// opening a door takes a turn of its own before stepping through it
+const DOOR_COST: u32 = 2;
+
+/// How many turns it takes one kind of creature to get to the nearest of some targets from around
+/// them, ignoring anything in the way. Every monster of that kind heading for the same place
+/// shares one of these, so a pack chasing the player only needs it worked out once. It only gets
+/// worked out as far as it needs to be, so a monster ambling somewhere close by doesn't search the
+/// whole level.
+#[derive(Clone)]
+pub(super) struct DistanceMap {
+    actor: ActorType,
+    distances: HashMap<Position, u32>,
+    // where the distance is known for sure, since nothing left to look at could be any closer
+    settled: HashSet<Position>,
+    queue: BinaryHeap<Reverse<(u32, Position)>>,
+}
+
+impl DistanceMap {
+    fn new(actor: ActorType, targets: impl IntoIterator<Item = Position>) -> DistanceMap {
+        let mut map = DistanceMap {
+            actor,
+            distances: HashMap::new(),
+            settled: HashSet::new(),
+            queue: BinaryHeap::new(),
+        };
+        for target in targets {
+            map.distances.insert(target, 0);
+            map.queue.push(Reverse((0, target)));
+        }
+        map
+    }
+
+    /// The distance from somewhere over some terrain, searching further out from the targets
+    /// until it's known.
+    fn distance(&mut self, terrain: &impl Fn(Position) -> Tile, pos: Position) -> Option<u32> {
+        // Moves can be made in either direction whenever the destination is passable, so
+        // working outwards from the targets gives the distance to them.
+        while !self.settled.contains(&pos) {
+            let Reverse((dist, next)) = self.queue.pop()?;
+            if !self.settled.insert(next) {
+                continue;
+            }
+            // a target might be something to attack or explore rather than somewhere to stand
+            let cost = entry_cost(terrain(next), self.actor).unwrap_or(1);
+            for from in moves(terrain, self.actor, next) {
+                if entry_cost(terrain(from), self.actor).is_none() {
+                    continue;
+                }
+                let from_dist = dist + cost;
+                if self.distances.get(&from).map_or(true, |&d| d > from_dist) {
+                    self.distances.insert(from, from_dist);
+                    self.queue.push(Reverse((from_dist, from)));
+                }
+            }
+        }
+        self.distances.get(&pos).cloned()
+    }
+}
+
+/// Turns taken to step onto a tile, or `None` if there's no getting onto it.
+fn entry_cost(tile: Tile, actor: ActorType) -> Option<u32> {
+    if tile.passable_for(actor) {
+        Some(1)
+    } else if tile == Tile::ClosedDoor && actor.def().opens_doors {
+        Some(DOOR_COST)
+    } else {
+        None
+    }
+}
+
+/// Where a knight moving creature could jump to, clockwise from straight up and to the left.
+pub(super) fn knight_moves(pos: Position) -> [Position; 8] {
+    use Direction::*;
+    [
+        pos.step(North).step(NorthWest),
+        pos.step(North).step(NorthEast),
+        pos.step(East).step(NorthEast),
+        pos.step(East).step(SouthEast),
+        pos.step(South).step(SouthEast),
+        pos.step(South).step(SouthWest),
+        pos.step(West).step(SouthWest),
+        pos.step(West).step(NorthWest),
+    ]
+}
+
+/// Everywhere a creature could move to from a position if the destination were free. Only the
+/// way there is checked, and that's the same both ways, so these are also the places it could
+/// come from.
+fn moves(terrain: &impl Fn(Position) -> Tile, actor: ActorType, pos: Position) -> Vec<Position> {
+    match actor.def().movement {
+        Movement::Knight => knight_moves(pos).iter().cloned()
+            .filter(|&dest| super::fov::has_line_of_sight(pos, dest, |pos| terrain(pos).obstruction()))
+            .collect(),
+        movement => ALL_DIRECTIONS.iter().cloned()
+            .filter(|dir| movement == Movement::Any || dir.is_orthogonal())
+            .filter(|&dir| !squeezed(terrain, pos, dir))
+            .map(|dir| pos.step(dir))
+            .collect(),
+    }
+}
+
+/// Whether a diagonal move would squeeze between two tiles that completely block sight.
+fn squeezed(terrain: &impl Fn(Position) -> Tile, pos: Position, dir: Direction) -> bool {
+    let (a, b) = match dir {
+        Direction::NorthEast => (Direction::North, Direction::East),
+        Direction::SouthEast => (Direction::South, Direction::East),
+        Direction::SouthWest => (Direction::South, Direction::West),
+        Direction::NorthWest => (Direction::North, Direction::West),
+        _ => { return false; }
+    };
+    terrain(pos.step(a)).obstruction() == Obstruction::Full
+        && terrain(pos.step(b)).obstruction() == Obstruction::Full
+}
+
+impl Game {
+    pub(super) fn squeezed(&self, pos: Position, dir: Direction) -> bool {
+        squeezed(&|pos| self.tile(pos), pos, dir)
+    }
+
+    /// Moves which get a creature closer to a target, best first, or nothing if it can't get
+    /// there from where it is. Moves that are as good as each other come in a random order,
+    /// with ones closer to a straight line first.
+    pub(super) fn next_steps(&mut self, actor: ActorType, from: Position, to: Position) -> Vec<Position> {
+        // taken out while it's being added to, since that needs to look at the level
+        let mut map = self.distance_maps.remove(&(actor, to))
+            .unwrap_or_else(|| DistanceMap::new(actor, Some(to)));
+        let terrain = |pos| self.tile(pos);
+        let dist = map.distance(&terrain, from);
+        let moves = moves(&terrain, actor, from);
+        self.distance_maps.insert((actor, to), map);
+        let dist = match dist {
+            Some(dist) => dist,
+            None => { return Vec::new(); }
+        };
+        // anywhere closer than here has already been settled on the way
+        let map = &self.distance_maps[&(actor, to)];
+        let mut steps: Vec<_> = moves.into_iter()
+            .filter_map(|pos| Some((map.distances.get(&pos).cloned().filter(|&d| d < dist)?, pos)))
+            .collect();
+        steps.shuffle(&mut self.rng);
+        let straightness = |pos: Position| (pos.x - to.x).pow(2) + (pos.y - to.y).pow(2);
+        steps.sort_by_key(|&(dist, pos)| (dist, straightness(pos)));
+        steps.into_iter().map(|(_, pos)| pos).collect()
+    }
+
+    /// Which way the player should step to get to the nearest of some places, going only by what
+    /// they've seen. Opening doors on the way counts as moving.
+    fn player_direction(&self, targets: impl IntoIterator<Item = Position>) -> Option<Direction> {
+        let pos = self.player_position()?;
+        let mut map = DistanceMap::new(ActorType::PLAYER, targets);
+        let terrain = |pos| self.view(pos).tile().unwrap_or(Tile::Wall);
+        let dist = map.distance(&terrain, pos)?;
+        // anywhere closer than here has already been settled on the way
+        ALL_DIRECTIONS.iter().cloned()
+            .filter(|&dir| !squeezed(&terrain, pos, dir))
+            .filter_map(|dir| Some((map.distances.get(&pos.step(dir)).cloned().filter(|&d| d < dist)?, dir)))
+            .min_by_key(|&(dist, _)| dist)
+            .map(|(_, dir)| dir)
+    }
+
+    /// Which way to go to get to the nearest unexplored spot the player knows a way to.
+    pub fn explore_direction(&self) -> Option<Direction> {
+        // Nothing gets marked as explorable behind closed doors or around rubble, so go and
+        // stand anywhere next to something unknown as well.
+        let next_to_unknown = |pos: Position, view: TileView| view.tile().is_some_and(Tile::walkable)
+            && ORTHOGONAL_DIRECTIONS.iter().any(|&dir| self.view(pos.step(dir)) == TileView::Unknown);
+        let targets: Vec<_> = self.view.iter()
+            .filter(|&(&pos, &view)| view == TileView::Explorable || next_to_unknown(pos, view))
+            .map(|(&pos, _)| pos)
+            .collect();
+        self.player_direction(targets)
+    }
+
+    /// Which way to go to get somewhere the player has seen, if they know a way there.
+    pub fn travel_direction(&self, to: Position) -> Option<Direction> {
+        self.view(to).tile()?;
+        self.player_direction(Some(to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // open ground out to a wall, with some other tiles in it
+    fn terrain(tiles: &[((i32, i32), Tile)]) -> impl Fn(Position) -> Tile + '_ {
+        move |pos| match tiles.iter().find(|&&((x, y), _)| pos == Position { x, y }) {
+            Some(&(_, tile)) => tile,
+            None if pos.x.abs() <= 10 && pos.y.abs() <= 10 => Tile::Ground,
+            None => Tile::Wall,
+        }
+    }
+
+    fn distance(actor: &str, tiles: &[((i32, i32), Tile)], from: (i32, i32), to: (i32, i32)) -> Option<u32> {
+        let actor = ActorType::named(actor).unwrap();
+        let mut map = DistanceMap::new(actor, Some(Position { x: to.0, y: to.1 }));
+        map.distance(&terrain(tiles), Position { x: from.0, y: from.1 })
+    }
+
+    #[test]
+    fn goes_around_walls() {
+        let wall = [((1, -1), Tile::Wall), ((1, 0), Tile::Wall), ((1, 1), Tile::Wall)];
+        assert_eq!(distance("wolf", &[], (0, 0), (2, 0)), Some(2));
+        assert_eq!(distance("wolf", &wall, (0, 0), (2, 0)), Some(4));
+
+        let mut g = Game::new(0);
+        g.tiles = (-10..=10).flat_map(|x| (-10..=10).map(move |y| Position { x, y }))
+            .map(|pos| (pos, terrain(&wall)(pos)))
+            .collect();
+        let wolf = ActorType::named("wolf").unwrap();
+        let mut steps = g.next_steps(wolf, Position { x: 0, y: 0 }, Position { x: 2, y: 0 });
+        steps.sort();
+        assert_eq!(steps, vec![Position { x: 0, y: -1 }, Position { x: 0, y: 1 }]);
+    }
+
+    #[test]
+    fn crabs_only_move_orthogonally() {
+        assert_eq!(distance("wolf", &[], (0, 0), (2, 2)), Some(2));
+        assert_eq!(distance("crab", &[], (0, 0), (2, 2)), Some(4));
+    }
+
+    #[test]
+    fn no_squeezing_between_walls() {
+        let walls = [((1, 0), Tile::Wall), ((0, 1), Tile::Wall)];
+        let trees = [((1, 0), Tile::Tree), ((0, 1), Tile::Tree)];
+        assert_eq!(distance("wolf", &walls, (0, 0), (1, 1)), Some(3));
+        assert_eq!(distance("wolf", &trees, (0, 0), (1, 1)), Some(1));
+    }
+
+    #[test]
+    fn knights_jump() {
+        assert_eq!(distance("dragonfly", &[], (0, 0), (1, 2)), Some(1));
+        assert_eq!(distance("dragonfly", &[], (0, 0), (1, 1)), Some(2));
+        // it has to be able to see where it's going, but the line there can go either side
+        let one_side = [((0, 1), Tile::Wall)];
+        let both_sides = [((0, 1), Tile::Wall), ((1, 1), Tile::Tree)];
+        assert_eq!(distance("dragonfly", &one_side, (0, 0), (1, 2)), Some(1));
+        assert!(distance("dragonfly", &both_sides, (0, 0), (1, 2)) > Some(1));
+    }
+}
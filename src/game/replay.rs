@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Action, ActionError, Game, MapKind};
+
+// Bump this whenever game logic changes in a way that would desync old replays.
+const REPLAY_VERSION: u32 = 17;
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    version: u32,
+    seed: u64,
+    map_kind: MapKind,
+    actions: Vec<Action>,
+    checksum: u64,
+    // undone turns are left out of the replay, so it needs to remember they happened
+    scoring: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ReplayError {
+    WrongVersion(u32),
+    Malformed(String),
+    ActionFailed { step: usize, error: ActionError },
+    ChecksumMismatch,
+}
+
+impl Replay {
+    pub fn parse(replay: &str) -> Result<Replay, ReplayError> {
+        let replay: Replay = ron::de::from_str(replay)
+            .map_err(|e| ReplayError::Malformed(e.to_string()))?;
+        if replay.version != REPLAY_VERSION {
+            return Err(ReplayError::WrongVersion(replay.version));
+        }
+        Ok(replay)
+    }
+
+    pub fn serialize(&self) -> String {
+        ron::ser::to_string(self).expect("failed to serialize replay")
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn map_kind(&self) -> MapKind {
+        self.map_kind
+    }
+
+    pub fn is_scoring(&self) -> bool {
+        self.scoring
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Plays back the whole replay and checks that it ends up in the recorded state.
+    pub fn verify(&self) -> Result<(), ReplayError> {
+        let mut playback = Playback::new(self.clone());
+        while let Some(result) = playback.step() {
+            result?;
+        }
+        playback.verify()
+    }
+}
+
+pub struct Playback {
+    replay: Replay,
+    game: Game,
+    next_step: usize,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Playback {
+        Playback {
+            game: Game::new_with_map(replay.seed, replay.map_kind),
+            replay,
+            next_step: 0,
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn next_step(&self) -> usize {
+        self.next_step
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_step >= self.replay.actions.len()
+    }
+
+    /// Replays the next recorded action, or returns `None` if there are no more.
+    pub fn step(&mut self) -> Option<Result<(), ReplayError>> {
+        let step = self.next_step;
+        let &action = self.replay.actions.get(step)?;
+        self.next_step += 1;
+        Some(self.game.take_player_action(action)
+            .map_err(|error| ReplayError::ActionFailed { step, error }))
+    }
+
+    /// Checks the current state against the recording; only meaningful once finished.
+    pub fn verify(&self) -> Result<(), ReplayError> {
+        if self.game.checksum() == self.replay.checksum {
+            Ok(())
+        } else {
+            Err(ReplayError::ChecksumMismatch)
+        }
+    }
+}
+
+// FNV-1a, since the std hashers aren't guaranteed to be stable between releases
+struct StableHasher(u64);
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+fn sorted<K: Ord, V>(map: &HashMap<K, V>) -> Vec<(&K, &V)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+impl Game {
+    pub fn replay(&self) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            seed: self.seed,
+            map_kind: self.map_kind,
+            actions: self.actions.clone(),
+            checksum: self.checksum(),
+            scoring: self.is_scoring(),
+        }
+    }
+
+    pub(super) fn checksum(&self) -> u64 {
+        let mut h = StableHasher(0xcbf2_9ce4_8422_2325);
+        sorted(&self.tiles).hash(&mut h);
+        sorted(&self.types).hash(&mut h);
+        self.states.hash(&mut h);
+        sorted(&self.positions).hash(&mut h);
+        sorted(&self.objects).hash(&mut h);
+        let mut ghosts: Vec<_> = self.revealed_actors.iter().collect();
+        ghosts.sort();
+        ghosts.hash(&mut h);
+        self.inventory.hash(&mut h);
+        sorted(&self.health).hash(&mut h);
+        sorted(&self.energy).hash(&mut h);
+        sorted(&self.statuses).hash(&mut h);
+        sorted(&self.homes).hash(&mut h);
+        self.sneaking.hash(&mut h);
+        sorted(&self.kills).hash(&mut h);
+        self.rng.word_pos().hash(&mut h);
+        self.prev_entity.hash(&mut h);
+        sorted(&self.view).hash(&mut h);
+        self.depth.hash(&mut h);
+        for (depth, level) in &self.levels {
+            depth.hash(&mut h);
+            sorted(&level.tiles).hash(&mut h);
+            level.states.hash(&mut h);
+            sorted(&level.positions).hash(&mut h);
+            sorted(&level.objects).hash(&mut h);
+            let mut ghosts: Vec<_> = level.revealed_actors.iter().collect();
+            ghosts.sort();
+            ghosts.hash(&mut h);
+            sorted(&level.health).hash(&mut h);
+            sorted(&level.energy).hash(&mut h);
+            sorted(&level.statuses).hash(&mut h);
+            sorted(&level.homes).hash(&mut h);
+            sorted(&level.view).hash(&mut h);
+        }
+        h.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use super::*;
+    use crate::game::play_randomly;
+    use crate::game::rng::GameRng;
+
+    fn record(seed: u64) -> Replay {
+        let mut g = Game::new_with_map(seed, MapKind::default());
+        play_randomly(&mut g, &mut GameRng::seed_from_u64(seed), 200);
+        g.replay()
+    }
+
+    #[test]
+    fn recorded_runs_verify() {
+        for seed in 0..10 {
+            let replay = record(seed);
+            assert!(!replay.is_empty());
+            assert_eq!(replay.verify(), Ok(()), "seed {}", seed);
+            assert_eq!(Replay::parse(&replay.serialize()), Ok(replay));
+        }
+    }
+
+    #[test]
+    fn tampered_runs_fail() {
+        for seed in 0..10 {
+            let mut replay = record(seed);
+            // waiting instead of doing anything else leaves the game in a different state
+            let i = replay.actions.iter().position(|&action| action != Action::Wait).unwrap();
+            replay.actions[i] = Action::Wait;
+            assert!(replay.verify().is_err(), "seed {}", seed);
+        }
+    }
+}
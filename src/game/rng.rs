@@ -0,0 +1,68 @@
+use rand::prelude::*;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// StdRng doesn't expose its internal state, so use the underlying generator directly and remember
+// the seed so it can be saved as (seed, word position).
+#[derive(Clone)]
+pub struct GameRng {
+    seed: [u8; 32],
+    rng: ChaCha20Rng,
+}
+
+impl GameRng {
+    pub fn word_pos(&self) -> u128 {
+        self.rng.get_word_pos()
+    }
+}
+
+impl SeedableRng for GameRng {
+    type Seed = [u8; 32];
+
+    fn from_seed(seed: [u8; 32]) -> GameRng {
+        GameRng { seed, rng: ChaCha20Rng::from_seed(seed) }
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+// The word position is really a u128, but no game is ever going to get through 2^64 words.
+#[derive(Serialize, Deserialize)]
+struct RngState {
+    seed: [u8; 32],
+    word_pos: u64,
+}
+
+impl Serialize for GameRng {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RngState {
+            seed: self.seed,
+            word_pos: self.rng.get_word_pos() as u64,
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GameRng {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<GameRng, D::Error> {
+        let state = RngState::deserialize(deserializer)?;
+        let mut rng = GameRng::from_seed(state.seed);
+        rng.rng.set_word_pos(state.word_pos as u128);
+        Ok(rng)
+    }
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use super::Game;
+
+// Bump this whenever the serialized layout of Game changes.
+const SAVE_VERSION: u32 = 16;
+
+#[derive(Serialize)]
+struct SaveFile<'a> {
+    version: u32,
+    game: &'a Game,
+}
+
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct LoadFile {
+    game: Game,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LoadError {
+    WrongVersion(u32),
+    Malformed(String),
+}
+
+impl Game {
+    pub fn save(&self) -> String {
+        ron::ser::to_string(&SaveFile { version: SAVE_VERSION, game: self })
+            // TODO: is this actually unreachable?
+            .expect("failed to serialize game")
+    }
+
+    pub fn load(save: &str) -> Result<Game, LoadError> {
+        // check the version first so old saves get a sensible error instead of a parse failure
+        let header: SaveHeader = ron::de::from_str(save)
+            .map_err(|e| LoadError::Malformed(e.to_string()))?;
+        if header.version != SAVE_VERSION {
+            return Err(LoadError::WrongVersion(header.version));
+        }
+        let mut file: LoadFile = ron::de::from_str(save)
+            .map_err(|e| LoadError::Malformed(e.to_string()))?;
+        // turns from before the game was saved can't be undone
+        file.game.undo_from = file.game.actions.len();
+        Ok(file.game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use crate::game::{Game, MapKind, play_randomly};
+    use crate::game::rng::GameRng;
+
+    #[test]
+    fn resumed_games_continue_identically() {
+        for &kind in &MapKind::ALL {
+            for seed in 0..5 {
+                let mut uninterrupted = Game::new_with_map(seed, kind);
+                let mut actions = GameRng::seed_from_u64(seed);
+                play_randomly(&mut uninterrupted, &mut actions, 50);
+                let mut resumed = Game::load(&uninterrupted.save()).expect("failed to load save");
+                assert_eq!(resumed.checksum(), uninterrupted.checksum());
+
+                let mut more_actions = actions.clone();
+                play_randomly(&mut uninterrupted, &mut actions, 100);
+                play_randomly(&mut resumed, &mut more_actions, 100);
+                assert_eq!(resumed.checksum(), uninterrupted.checksum(), "{} seed {}", kind.name(), seed);
+                assert_eq!(resumed.replay(), uninterrupted.replay());
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::mem;
+
+use super::Game;
+
+// How many turns apart the copies of the game that undo goes back to are. Anything in between is
+// played again from the copy before it, so this trades memory against how long undoing takes.
+const CHECKPOINT_TURNS: usize = 50;
+
+impl Game {
+    // The turn counts as part of the checkpoint but the actions leading up to it don't, since
+    // they're the same as the start of this game's and keeping them would make checkpoints grow
+    // as the game goes on.
+    pub(super) fn checkpoint(&mut self) {
+        let turn = self.actions.len();
+        let due = match self.checkpoints.last() {
+            Some(&(last, _)) => turn >= last + CHECKPOINT_TURNS,
+            None => true,
+        };
+        if !due {
+            return;
+        }
+        let actions = mem::take(&mut self.actions);
+        let checkpoints = mem::take(&mut self.checkpoints);
+        let mut checkpoint = self.clone();
+        checkpoint.distance_maps.clear();
+        self.actions = actions;
+        self.checkpoints = checkpoints;
+        self.checkpoints.push((turn, checkpoint));
+    }
+
+    pub fn is_practice(&self) -> bool {
+        self.practice
+    }
+
+    /// In practice mode every turn can be undone, but runs which actually use it don't count.
+    pub fn set_practice(&mut self, practice: bool) {
+        if practice && !self.practice {
+            self.undo_from = self.actions.len();
+        }
+        self.practice = practice;
+        if !practice {
+            self.checkpoints.clear();
+        }
+    }
+
+    pub fn is_scoring(&self) -> bool {
+        !self.used_undo
+    }
+
+    pub fn undo_turns(&self) -> usize {
+        if self.practice {
+            self.actions.len() - self.undo_from
+        } else {
+            0
+        }
+    }
+
+    /// Rewinds up to `turns` turns, returning how many were actually undone.
+    pub fn undo(&mut self, turns: usize) -> usize {
+        let turns = turns.min(self.undo_turns());
+        if turns == 0 {
+            return 0;
+        }
+        let target = self.actions.len() - turns;
+        let mut checkpoints = mem::take(&mut self.checkpoints);
+        let keep = checkpoints.iter().rposition(|&(turn, _)| turn <= target)
+            .expect("no checkpoint to undo to");
+        checkpoints.truncate(keep + 1);
+        let (turn, ref checkpoint) = checkpoints[keep];
+
+        let mut rewound = checkpoint.clone();
+        rewound.actions = self.actions[..turn].to_vec();
+        rewound.practice = false;
+        for &action in &self.actions[turn..target] {
+            // these all worked the first time round, so they will again
+            let _ = rewound.take_player_action(action);
+        }
+        rewound.events.clear();
+        rewound.practice = true;
+        rewound.used_undo = true;
+        rewound.undo_from = self.undo_from;
+        rewound.checkpoints = checkpoints;
+        *self = rewound;
+        turns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use crate::game::{Game, MapKind, play_randomly};
+    use crate::game::rng::GameRng;
+
+    #[test]
+    fn undo_goes_back_to_the_same_state() {
+        for &kind in &MapKind::ALL {
+            for seed in 0..10 {
+                let mut g = Game::new_with_map(seed, kind);
+                let mut rng = GameRng::seed_from_u64(seed);
+                play_randomly(&mut g, &mut rng, 20);
+                g.set_practice(true);
+                let practice_from = g.turns();
+                play_randomly(&mut g, &mut rng, 60);
+                let checkpoint = g.checksum();
+                let turns = g.turns();
+                play_randomly(&mut g, &mut rng, 70);
+
+                let undone = g.turns() - turns;
+                assert_eq!(g.undo(undone), undone);
+                assert_eq!(g.checksum(), checkpoint, "{} seed {}", kind.name(), seed);
+                assert_eq!(g.is_scoring(), undone == 0);
+                // only turns taken in practice mode can be undone
+                assert_eq!(g.undo(100), turns - practice_from);
+                assert_eq!(g.turns(), practice_from);
+            }
+        }
+    }
+}
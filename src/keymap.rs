@@ -0,0 +1,338 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use cursive::event::{Event, Key};
+
+use crate::game::geometry::Direction;
+
+const BUILTIN_KEYMAP: &str = include_str!("../data/keymap.toml");
+
+static KEYMAP: OnceLock<Keymap> = OnceLock::new();
+
+/// Something the player can ask for from the map.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    Move(Direction),
+    Wait,
+    PickUp,
+    Harvest,
+    CloseDoor,
+    Descend,
+    Ascend,
+    Sneak,
+    Explore,
+    Inventory,
+    Look,
+    Pan(Direction),
+    Camera,
+    Messages,
+    Help,
+    Practice,
+    Undo,
+    UndoTen,
+    Restart,
+    Debug,
+    Quit,
+    PauseReplay,
+    StepReplay,
+    FastForward,
+}
+
+/// Where a command does anything. The same key can do different things in different places.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Context {
+    Game,
+    Replay,
+    Everywhere,
+}
+
+impl Context {
+    fn overlaps(self, other: Context) -> bool {
+        self == other || self == Context::Everywhere || other == Context::Everywhere
+    }
+}
+
+impl Command {
+    fn context(self) -> Context {
+        match self {
+            Command::Debug | Command::Quit => Context::Everywhere,
+            Command::PauseReplay | Command::StepReplay | Command::FastForward => Context::Replay,
+            _ => Context::Game,
+        }
+    }
+}
+
+/// Every command with its name in keymap files and what it does, in the order they're listed
+/// on the help screen.
+const COMMANDS: &[(Command, &str, &str)] = {
+    use Command::*;
+    use Direction::*;
+    &[
+        (Move(North), "move_north", "move or attack north"),
+        (Move(NorthEast), "move_north_east", "move or attack northeast"),
+        (Move(East), "move_east", "move or attack east"),
+        (Move(SouthEast), "move_south_east", "move or attack southeast"),
+        (Move(South), "move_south", "move or attack south"),
+        (Move(SouthWest), "move_south_west", "move or attack southwest"),
+        (Move(West), "move_west", "move or attack west"),
+        (Move(NorthWest), "move_north_west", "move or attack northwest"),
+        (Wait, "wait", "wait a turn"),
+        (PickUp, "pick_up", "pick up what's here"),
+        (Harvest, "harvest", "harvest a corpse"),
+        (CloseDoor, "close_door", "close a door next to you"),
+        (Descend, "descend", "go down stairs"),
+        (Ascend, "ascend", "go up stairs"),
+        (Sneak, "sneak", "start or stop sneaking"),
+        (Explore, "explore", "explore until something turns up"),
+        (Inventory, "inventory", "use an item"),
+        (Look, "look", "look around"),
+        (Pan(North), "pan_north", "pan the view north"),
+        (Pan(East), "pan_east", "pan the view east"),
+        (Pan(South), "pan_south", "pan the view south"),
+        (Pan(West), "pan_west", "pan the view west"),
+        (Camera, "camera", "change how the view follows you"),
+        (Messages, "messages", "show old messages"),
+        (Help, "help", "show this help"),
+        (Practice, "practice", "switch to practice mode"),
+        (Undo, "undo", "undo a turn in practice mode"),
+        (UndoTen, "undo_ten", "undo ten turns in practice mode"),
+        (Restart, "restart", "start a new game"),
+        (Debug, "debug", "show what monsters are thinking"),
+        (Quit, "quit", "save and quit"),
+        (PauseReplay, "replay_pause", "pause or carry on watching a replay"),
+        (StepReplay, "replay_step", "step through a replay a turn at a time"),
+        (FastForward, "replay_fast_forward", "fast forward through a replay"),
+    ]
+};
+
+const KEY_NAMES: &[(Key, &str)] = &[
+    (Key::Up, "Up"),
+    (Key::Down, "Down"),
+    (Key::Left, "Left"),
+    (Key::Right, "Right"),
+    (Key::Enter, "Enter"),
+    (Key::Tab, "Tab"),
+    (Key::Backspace, "Backspace"),
+    (Key::Esc, "Esc"),
+    (Key::Ins, "Ins"),
+    (Key::Del, "Del"),
+    (Key::Home, "Home"),
+    (Key::End, "End"),
+    (Key::PageUp, "PageUp"),
+    (Key::PageDown, "PageDown"),
+    (Key::PauseBreak, "PauseBreak"),
+    (Key::NumpadCenter, "NumpadCenter"),
+    (Key::F1, "F1"),
+    (Key::F2, "F2"),
+    (Key::F3, "F3"),
+    (Key::F4, "F4"),
+    (Key::F5, "F5"),
+    (Key::F6, "F6"),
+    (Key::F7, "F7"),
+    (Key::F8, "F8"),
+    (Key::F9, "F9"),
+    (Key::F10, "F10"),
+    (Key::F11, "F11"),
+    (Key::F12, "F12"),
+];
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum KeymapError {
+    Malformed(String),
+    Invalid(String),
+    /// Keys bound to more than one command, with the commands they're bound to.
+    Conflicts(Vec<(String, Vec<String>)>),
+    AlreadyLoaded,
+}
+
+/// Reads a key as written in a keymap file.
+fn parse_key(key: &str) -> Option<Event> {
+    let named = |name: &str| KEY_NAMES.iter().find(|&&(_, n)| n == name).map(|&(key, _)| key);
+    let single = |s: &str| {
+        // written out since a bare space is easy to miss
+        if s == "Space" {
+            return Some(' ');
+        }
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Some(ch),
+            _ => None,
+        }
+    };
+    if let Some(ch) = single(key) {
+        return Some(Event::Char(ch));
+    }
+    if let Some(key) = named(key) {
+        return Some(Event::Key(key));
+    }
+    let (modifier, rest) = key.split_once('+')?;
+    match (modifier, single(rest), named(rest)) {
+        ("Ctrl", Some(ch), _) => Some(Event::CtrlChar(ch)),
+        ("Alt", Some(ch), _) => Some(Event::AltChar(ch)),
+        ("Ctrl", _, Some(key)) => Some(Event::Ctrl(key)),
+        ("Alt", _, Some(key)) => Some(Event::Alt(key)),
+        ("Shift", _, Some(key)) => Some(Event::Shift(key)),
+        _ => None,
+    }
+}
+
+/// Which keys do what.
+pub struct Keymap {
+    // the keys for each command as they were written, in the same order as `COMMANDS`
+    keys: Vec<Vec<String>>,
+    commands: HashMap<Event, Vec<Command>>,
+}
+
+impl Keymap {
+    /// Reads a keymap, using the built in keys for any command it leaves out.
+    fn parse(toml: &str, defaults: Option<&Keymap>) -> Result<Keymap, KeymapError> {
+        let mut bindings: BTreeMap<String, Vec<String>> = toml::from_str(toml)
+            .map_err(|e| KeymapError::Malformed(e.to_string()))?;
+        let keys: Vec<Vec<String>> = COMMANDS.iter().enumerate().map(|(idx, &(_, name, _))| {
+            match (bindings.remove(name), defaults) {
+                (Some(keys), _) => keys,
+                (None, Some(defaults)) => defaults.keys[idx].clone(),
+                (None, None) => Vec::new(),
+            }
+        }).collect();
+        if let Some(name) = bindings.keys().next() {
+            return Err(KeymapError::Invalid(format!("there's no command called {}", name)));
+        }
+
+        // the commands for each key, going by the key itself rather than how it was written
+        let mut bound: HashMap<Event, (&str, Vec<usize>)> = HashMap::new();
+        for (idx, command_keys) in keys.iter().enumerate() {
+            for key in command_keys {
+                let event = parse_key(key)
+                    .ok_or_else(|| KeymapError::Invalid(format!("{} isn't a key", key)))?;
+                let (_, commands) = bound.entry(event).or_insert((key, Vec::new()));
+                if !commands.contains(&idx) {
+                    commands.push(idx);
+                }
+            }
+        }
+        let context = |idx: usize| COMMANDS[idx].0.context();
+        let mut conflicts: Vec<_> = bound.values()
+            .map(|(key, commands)| {
+                let clashing: Vec<_> = commands.iter()
+                    .filter(|&&a| commands.iter().any(|&b| a != b && context(a).overlaps(context(b))))
+                    .map(|&idx| COMMANDS[idx].1.to_string())
+                    .collect();
+                (key.to_string(), clashing)
+            })
+            .filter(|(_, clashing)| !clashing.is_empty())
+            .collect();
+        conflicts.sort();
+        if !conflicts.is_empty() {
+            return Err(KeymapError::Conflicts(conflicts));
+        }
+        let commands = bound.into_iter()
+            .map(|(event, (_, commands))| (event, commands.iter().map(|&idx| COMMANDS[idx].0).collect()))
+            .collect();
+        Ok(Keymap { keys, commands })
+    }
+
+    pub fn command(&self, context: Context, ev: &Event) -> Option<Command> {
+        self.commands.get(ev)?.iter().cloned().find(|command| command.context().overlaps(context))
+    }
+
+    /// Every key for a command, for things that have to be set up ahead of time.
+    pub fn events(&self, command: Command) -> Vec<Event> {
+        COMMANDS.iter().position(|&(c, _, _)| c == command)
+            .map(|idx| self.keys[idx].iter().filter_map(|key| parse_key(key)).collect())
+            .unwrap_or_default()
+    }
+
+    /// The first key for a command, for telling the player about it.
+    pub fn key(&self, command: Command) -> &str {
+        COMMANDS.iter().position(|&(c, _, _)| c == command)
+            .and_then(|idx| self.keys[idx].first())
+            .map_or("(unbound)", String::as_str)
+    }
+
+    /// What every command does and the keys for it, one per line.
+    pub fn help(&self) -> String {
+        let lines: Vec<_> = COMMANDS.iter().zip(&self.keys)
+            .map(|(&(_, _, description), keys)| {
+                let keys = if keys.is_empty() { "(unbound)".to_string() } else { keys.join(" ") };
+                format!("{:<18} {}", keys, description)
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+fn builtin_keymap() -> Keymap {
+    Keymap::parse(BUILTIN_KEYMAP, None).expect("invalid built in keymap")
+}
+
+pub fn keymap() -> &'static Keymap {
+    KEYMAP.get_or_init(builtin_keymap)
+}
+
+/// Changes which keys do what, keeping the built in keys for commands the keymap leaves out.
+/// This has to happen before the game starts.
+pub fn load_keymap(toml: &str) -> Result<(), KeymapError> {
+    let keymap = Keymap::parse(toml, Some(&builtin_keymap()))?;
+    KEYMAP.set(keymap).map_err(|_| KeymapError::AlreadyLoaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_keymap_binds_everything() {
+        let keymap = builtin_keymap();
+        for &(command, name, _) in COMMANDS {
+            assert_ne!(keymap.key(command), "(unbound)", "{} isn't bound", name);
+        }
+        assert_eq!(keymap.command(Context::Game, &Event::Char('k')), Some(Command::Move(Direction::North)));
+        assert_eq!(keymap.command(Context::Game, &Event::Shift(Key::Up)), Some(Command::Pan(Direction::North)));
+    }
+
+    #[test]
+    fn keys_with_modifiers() {
+        assert_eq!(parse_key("Shift+Up"), Some(Event::Shift(Key::Up)));
+        assert_eq!(parse_key("Ctrl+x"), Some(Event::CtrlChar('x')));
+        assert_eq!(parse_key("Alt+Enter"), Some(Event::Alt(Key::Enter)));
+        assert_eq!(parse_key("Shift+x"), None);
+        assert_eq!(parse_key("Up+Shift"), None);
+
+        let keymap = Keymap::parse("wait = [\"Ctrl+x\"]\nlook = [\"Shift+Up\"]", None).unwrap();
+        assert_eq!(keymap.command(Context::Game, &Event::CtrlChar('x')), Some(Command::Wait));
+        assert_eq!(keymap.command(Context::Game, &Event::Shift(Key::Up)), Some(Command::Look));
+    }
+
+    #[test]
+    fn keys_can_only_do_one_thing() {
+        let defaults = builtin_keymap();
+        let conflicts = vec![("h".to_string(), vec!["move_west".to_string(), "wait".to_string()])];
+        assert_eq!(Keymap::parse("wait = [\"h\"]", Some(&defaults)).err(), Some(KeymapError::Conflicts(conflicts)));
+        // moving a key off the command it was on first is fine
+        assert!(Keymap::parse("wait = [\"h\"]\nmove_west = [\"Left\"]", Some(&defaults)).is_ok());
+    }
+
+    #[test]
+    fn replays_have_their_own_keys() {
+        let keymap = builtin_keymap();
+        assert_eq!(keymap.command(Context::Game, &Event::Char('.')), Some(Command::Wait));
+        assert_eq!(keymap.command(Context::Replay, &Event::Char('.')), Some(Command::StepReplay));
+        assert_eq!(keymap.command(Context::Replay, &Event::CtrlChar('q')), Some(Command::Quit));
+        // but quitting works everywhere, so nothing else can have its key
+        let conflicts = vec![("Ctrl+q".to_string(), vec!["wait".to_string(), "quit".to_string()])];
+        assert_eq!(
+            Keymap::parse("wait = [\"Ctrl+q\"]", Some(&keymap)).err(),
+            Some(KeymapError::Conflicts(conflicts)),
+        );
+    }
+
+    #[test]
+    fn unknown_commands_are_rejected() {
+        match Keymap::parse("dance = [\"d\"]", Some(&builtin_keymap())) {
+            Err(KeymapError::Invalid(_)) => {}
+            Err(e) => panic!("wrong error {:?}", e),
+            Ok(_) => panic!("no error"),
+        }
+    }
+}
@@ -6,25 +6,67 @@ use cursive::{
     Cursive,
     Printer,
     direction::Orientation,
-    event::{Event, EventResult, Key},
-    theme::{BaseColor, Color, ColorStyle},
+    event::{Event, EventResult, Key, MouseButton, MouseEvent},
+    theme::{BaseColor, Color, ColorStyle, Effect},
     vec::Vec2,
-    view::View,
-    views::{BoxView, LinearLayout},
+    view::{Identifiable, ScrollStrategy, View},
+    views::{BoxView, Dialog, LinearLayout, ScrollView, SelectView, TextView},
 };
 
-mod game;
+pub mod game;
+mod keymap;
+mod messages;
+mod sidebar;
 
 use game::{
     Action,
-    ActorType,
+    AiState,
     EntityType,
     Game,
+    MapKind,
+    ItemType,
+    LoadError,
     Obstruction,
+    Playback,
+    Replay,
+    ReplayError,
     Tile,
     TileView,
-    geometry::{Direction, Position},
+    geometry::{ALL_DIRECTIONS, Direction, Position},
 };
+pub use keymap::{KeymapError, load_keymap};
+use keymap::{Command, Context, keymap};
+use messages::{MessageLog, MessagePanel};
+use sidebar::Sidebar;
+
+/// How the view of the map keeps up with the player.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum CameraMode {
+    /// The player is always in the middle.
+    Centered,
+    /// The player gets put back in the middle when they come close to an edge.
+    Edge,
+    /// The view stays wherever it was panned to.
+    Manual,
+}
+
+impl CameraMode {
+    fn next(self) -> CameraMode {
+        match self {
+            CameraMode::Centered => CameraMode::Edge,
+            CameraMode::Edge => CameraMode::Manual,
+            CameraMode::Manual => CameraMode::Centered,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            CameraMode::Centered => "The view stays centered on you.",
+            CameraMode::Edge => "The view follows you when you get near an edge.",
+            CameraMode::Manual => "The view stays put unless you pan it.",
+        }
+    }
+}
 
 #[derive(Copy, Clone)]
 struct Camera {
@@ -34,6 +76,9 @@ struct Camera {
 }
 
 impl Camera {
+    // how close to an edge the player can get before the view catches up in edge mode
+    const EDGE_MARGIN: usize = 4;
+
     fn centered(size: Vec2, pos: Position) -> Camera {
         Camera {
             screen_size: size,
@@ -48,60 +93,394 @@ impl Camera {
             y: self.map_focus.y - self.screen_focus.y as i32 + offset.y as i32,
         }
     }
+
+    /// Where a tile is drawn, if it's on screen.
+    fn screen_position(&self, pos: Position) -> Option<Vec2> {
+        let x = pos.x - self.map_focus.x + self.screen_focus.x as i32;
+        let y = pos.y - self.map_focus.y + self.screen_focus.y as i32;
+        if x < 0 || y < 0 || x as usize >= self.screen_size.x || y as usize >= self.screen_size.y {
+            return None;
+        }
+        Some(Vec2::new(x as usize, y as usize))
+    }
+
+    /// Whether a tile is off screen or too close to an edge to see much past it.
+    fn near_edge(&self, pos: Position) -> bool {
+        let near = |at: usize, size: usize| {
+            let margin = Camera::EDGE_MARGIN.min(size / 2);
+            at < margin || at + margin >= size
+        };
+        match self.screen_position(pos) {
+            Some(at) => near(at.x, self.screen_size.x) || near(at.y, self.screen_size.y),
+            None => true,
+        }
+    }
+
+    /// Moves the view about a quarter of the screen.
+    fn panned(self, dir: Direction) -> Camera {
+        let step = Position { x: 0, y: 0 }.step(dir);
+        let (dx, dy) = ((self.screen_size.x / 4).max(1) as i32, (self.screen_size.y / 4).max(1) as i32);
+        Camera {
+            map_focus: Position { x: self.map_focus.x + step.x * dx, y: self.map_focus.y + step.y * dy },
+            ..self
+        }
+    }
+
+    /// Moves the view as little as possible so that it doesn't show anything past the edges of
+    /// the known map, or so that it shows all of it if it fits.
+    fn clamped(self, min: Position, max: Position) -> Camera {
+        let clamp = |focus: i32, screen_focus: usize, size: usize, min: i32, max: i32| {
+            // the first tile on screen, which can go from showing the start to showing the end
+            let first = focus - screen_focus as i32;
+            let (start, end) = (min, max - size as i32 + 1);
+            first.max(start.min(end)).min(start.max(end)) + screen_focus as i32
+        };
+        Camera {
+            map_focus: Position {
+                x: clamp(self.map_focus.x, self.screen_focus.x, self.screen_size.x, min.x, max.x),
+                y: clamp(self.map_focus.y, self.screen_focus.y, self.screen_size.y, min.y, max.y),
+            },
+            ..self
+        }
+    }
+
+    /// Where the view should be on a screen of some size, given where it was before.
+    fn follow(camera: Option<Camera>, mode: CameraMode, size: Vec2, game: &Game, player_pos: Position) -> Camera {
+        let cam = match camera {
+            None => Camera::centered(size, player_pos),
+            // keep the same part of the map in the middle
+            Some(cam) if cam.screen_size != size => Camera::centered(size, cam.map_focus),
+            Some(cam) => cam,
+        };
+        let cam = match mode {
+            CameraMode::Centered => { return Camera::centered(size, player_pos); }
+            CameraMode::Edge if cam.near_edge(player_pos) => Camera::centered(size, player_pos),
+            _ => cam,
+        };
+        let (mut min, mut max) = (player_pos, player_pos);
+        for (pos, _) in game.known_tiles() {
+            min = Position { x: min.x.min(pos.x), y: min.y.min(pos.y) };
+            max = Position { x: max.x.max(pos.x), y: max.y.max(pos.y) };
+        }
+        cam.clamped(min, max)
+    }
 }
 
 struct GameMap {
     game: Rc<RefCell<Game>>,
     camera: Cell<Option<Camera>>,
+    camera_mode: CameraMode,
+    // the tile under the mouse, which gets described until the next key press
+    hover: Option<Position>,
+    // where the cursor is while looking around
+    look: Option<Position>,
+    finished_runs: Rc<RefCell<Vec<Replay>>>,
+    log: Rc<RefCell<MessageLog>>,
+}
+
+/// Colors come from the creature definitions, so fall back to white rather than failing.
+fn parse_color(name: &str) -> Color {
+    // cursive panics on malformed hex colors instead of returning None
+    if name.starts_with('#') && name.len() != 4 && name.len() != 7 {
+        return Color::Light(BaseColor::White);
+    }
+    Color::parse(name).unwrap_or(Color::Light(BaseColor::White))
+}
+
+/// Returns whether anything happened that's worth mentioning.
+fn log_events(game: &mut Game, log: &mut MessageLog) -> bool {
+    let events: Vec<_> = game.drain_events().collect();
+    let messages = messages::describe_events(&events);
+    let eventful = !messages.is_empty();
+    for msg in messages {
+        log.push(msg);
+    }
+    if log.debug {
+        for msg in messages::describe_ai_changes(&events) {
+            log.push(msg);
+        }
+    }
+    eventful
+}
+
+/// Returns whether anything happened that's worth stopping for, including the action failing.
+fn take_turn(game: &mut Game, log: &mut MessageLog, action: Action) -> bool {
+    log.new_turn();
+    match game.take_player_action(action) {
+        Ok(()) => log_events(game, log),
+        Err(e) => {
+            let target = match (action, game.player_position()) {
+                (Action::Move(dir), Some(pos))
+                    | (Action::Attack(dir), Some(pos))
+                    | (Action::MoveAttack(dir), Some(pos)) => game.view(pos.step(dir)),
+                _ => TileView::Unknown,
+            };
+            log.push(messages::describe_error(action, e, target));
+            true
+        }
+    }
+}
+
+/// How many things on the level the player knows about that are worth going to look at.
+fn discoveries(game: &Game) -> usize {
+    game.known_tiles().filter(|&(_, view)| {
+        view.object().is_some() || matches!(view.tile(), Some(Tile::StairsDown) | Some(Tile::StairsUp))
+    }).count()
+}
+
+fn inventory_dialog(game: Rc<RefCell<Game>>, log: Rc<RefCell<MessageLog>>) -> Dialog {
+    let items = game.borrow().inventory();
+    if items.is_empty() {
+        return Dialog::info("You aren't carrying anything.");
+    }
+    let item_action = move |s: &mut Cursive, action: fn(usize) -> Action| {
+        let selection = s.call_on_id("inventory", |v: &mut SelectView<usize>| v.selection());
+        if let Some(idx) = selection.and_then(|idx| idx) {
+            s.pop_layer();
+            take_turn(&mut game.borrow_mut(), &mut log.borrow_mut(), action(*idx));
+        }
+    };
+    let use_item = item_action.clone();
+    let submit_item = item_action.clone();
+    let mut select = SelectView::new();
+    for (idx, &item) in items.iter().enumerate() {
+        select.add_item(messages::item_name(item), idx);
+    }
+    Dialog::around(select.on_submit(move |s, _| submit_item(s, Action::Use)).with_id("inventory"))
+        .title("Inventory")
+        .button("Use", move |s| use_item(s, Action::Use))
+        .button("Drop", move |s| item_action(s, Action::Drop))
+        .dismiss_button("Close")
 }
 
 impl GameMap {
-    fn render_tile(view: TileView) -> (&'static str, ColorStyle) {
+    fn log_events(&self, game: &mut Game) {
+        log_events(game, &mut self.log.borrow_mut());
+    }
+
+    fn do_action(&mut self, action: Action) -> EventResult {
+        let mut game = self.game.borrow_mut();
+        let depth = game.depth();
+        take_turn(&mut game, &mut self.log.borrow_mut(), action);
+        if game.depth() != depth {
+            // a new level has nothing to do with where the camera was on the old one
+            self.camera.set(None);
+        }
+        EventResult::Consumed(None)
+    }
+
+    /// Heads somewhere a turn at a time, or towards the nearest unexplored spot if there's nowhere
+    /// in particular to go. Stops when there's a monster in view, something new turns up, or
+    /// there's nowhere left to go.
+    fn travel(&mut self, dest: Option<Position>) -> EventResult {
+        let mut game = self.game.borrow_mut();
+        let mut log = self.log.borrow_mut();
+        let mut moved = false;
+        loop {
+            if let Some(&(_, monster, _)) = game.visible_monsters().first() {
+                // heading somewhere in particular still goes one careful step at a time
+                if moved {
+                    break;
+                } else if dest.is_none() {
+                    log.new_turn();
+                    log.push(format!("Not with {} around.", messages::a(monster)));
+                    break;
+                }
+            }
+            if dest.is_some() && game.player_position() == dest {
+                break;
+            }
+            let dir = match dest {
+                Some(dest) => game.travel_direction(dest),
+                None => game.explore_direction(),
+            };
+            let dir = match dir {
+                Some(dir) => dir,
+                None => {
+                    if !moved {
+                        log.new_turn();
+                    }
+                    log.push(match dest {
+                        Some(_) => "You don't know a way there.".to_string(),
+                        None => "There's nowhere left to explore.".to_string(),
+                    });
+                    break;
+                }
+            };
+            let known = discoveries(&game);
+            moved = true;
+            if take_turn(&mut game, &mut log, Action::MoveAttack(dir)) || discoveries(&game) > known {
+                break;
+            }
+        }
+        EventResult::Consumed(None)
+    }
+
+    fn look_around(&mut self, pos: Position, ev: Event) -> EventResult {
+        match (ev.clone(), keymap().command(Context::Game, &ev)) {
+            (Event::Key(Key::Esc), _) | (Event::Key(Key::Enter), _) | (_, Some(Command::Look)) => {
+                self.look = None;
+            }
+            (_, Some(Command::Move(dir))) => {
+                // keep the cursor on screen
+                let new_pos = pos.step(dir);
+                if self.camera.get().is_some_and(|cam| cam.screen_position(new_pos).is_some()) {
+                    self.look = Some(new_pos);
+                }
+            }
+            _ => {}
+        }
+        EventResult::Consumed(None)
+    }
+
+    fn on_mouse(&mut self, offset: Vec2, position: Vec2, event: MouseEvent) -> EventResult {
+        let cam = match self.camera.get() {
+            Some(cam) => cam,
+            None => { return EventResult::Ignored; }
+        };
+        let pos = match position.checked_sub(offset) {
+            Some(local) if local.x < cam.screen_size.x && local.y < cam.screen_size.y => {
+                cam.map_position(local)
+            }
+            _ => { return EventResult::Ignored; }
+        };
+        self.hover = Some(pos);
+        if event != MouseEvent::Press(MouseButton::Left) {
+            return EventResult::Consumed(None);
+        }
+        // clicking on a monster right next to the player attacks it, anywhere else is somewhere to go
+        let attack = {
+            let game = self.game.borrow();
+            game.player_position()
+                .filter(|&player_pos| player_pos.adjacent_to(pos) && game.view(pos).actor().is_some())
+                .and_then(|player_pos| {
+                    ALL_DIRECTIONS.iter().cloned().find(|&dir| player_pos.step(dir) == pos)
+                })
+        };
+        match attack {
+            Some(dir) => self.do_action(Action::Attack(dir)),
+            None => self.travel(Some(pos)),
+        }
+    }
+
+    fn draw_game(game: &Game, camera: &Cell<Option<Camera>>, mode: CameraMode, pr: &Printer, debug: bool) {
+        let player_pos = match game.player_position() {
+            Some(pos) => pos,
+            None => { return; }
+        };
+
+        let cam = Camera::follow(camera.get(), mode, pr.size, game, player_pos);
+        camera.set(Some(cam));
+        for x in 0..pr.size.x {
+            for y in 0..pr.size.y {
+                let pos = cam.map_position(Vec2 { x, y });
+                let (ch, color_style) = GameMap::render_tile(game.view(pos));
+                pr.with_color(color_style, |pr| {
+                    pr.print(Vec2::new(x, y), ch.encode_utf8(&mut [0; 4]));
+                });
+            }
+        }
+        if debug {
+            GameMap::draw_ai_states(game, cam, pr);
+        }
+        let style = ColorStyle::new(Color::Light(BaseColor::Red), Color::Dark(BaseColor::Black));
+        if let Some((hp, max_hp)) = game.player_health() {
+            let mut status = format!("HP {}/{}  Depth {}", hp, max_hp, game.depth());
+            if game.is_sneaking() {
+                status.push_str("  Sneaking");
+            }
+            pr.with_color(style, |pr| pr.print((0, 0), &status));
+        }
+        if !game.is_scoring() {
+            pr.with_color(style, |pr| pr.print((0, 1), "PRACTICE - NOT SCORED"));
+        }
+    }
+
+    /// Shows what every monster is doing in place of its glyph, whether the player can see it
+    /// or not.
+    fn draw_ai_states(game: &Game, cam: Camera, pr: &Printer) {
+        let style = ColorStyle::new(Color::Light(BaseColor::White), Color::Dark(BaseColor::Magenta));
+        for (pos, _, state) in game.ai_states() {
+            let screen_pos = match cam.screen_position(pos) {
+                Some(screen_pos) => screen_pos,
+                None => { continue; }
+            };
+            let ch = match state {
+                AiState::Wait => 'z',
+                AiState::Wander => 'w',
+                AiState::Guard => 'g',
+                AiState::Pursue => '!',
+                AiState::Search => '?',
+                AiState::Flee => 'f',
+            };
+            pr.with_color(style, |pr| pr.print(screen_pos, ch.encode_utf8(&mut [0; 4])));
+        }
+    }
+
+    fn undo(&mut self, turns: usize) -> EventResult {
+        let mut game = self.game.borrow_mut();
+        let mut log = self.log.borrow_mut();
+        log.new_turn();
+        if !game.is_practice() {
+            log.push(format!("You can only undo in practice mode (press {}).", keymap().key(Command::Practice)));
+        } else {
+            let depth = game.depth();
+            match game.undo(turns) {
+                0 => log.push("There's nothing to undo.".to_string()),
+                1 => log.push("You rewind one turn.".to_string()),
+                n => log.push(format!("You rewind {} turns.", n)),
+            }
+            if game.depth() != depth {
+                self.camera.set(None);
+            }
+        }
+        EventResult::Consumed(None)
+    }
+
+    fn render_tile(view: TileView) -> (char, ColorStyle) {
         // TODO: what if actor/object is embedded in a solid wall?
         let black_bg = |color| ColorStyle::new(color, Color::Dark(BaseColor::Black));
-        if let TileView::Visible { actor: Some(actor), .. } = view {
-            return match actor {
-                ActorType::Player => ("@", black_bg(Color::Light(BaseColor::White))),
-                ActorType::Rat => ("r", black_bg(Color::Light(BaseColor::White))),
-                ActorType::Wolf => ("w", black_bg(Color::Light(BaseColor::White))),
-                ActorType::Crab => ("c", black_bg(Color::Light(BaseColor::Red))),
-                ActorType::Beetle => ("b", black_bg(Color::Light(BaseColor::Cyan))),
-                ActorType::BigJelly => ("J", black_bg(Color::Light(BaseColor::Magenta))),
-                ActorType::LittleJelly => ("j", black_bg(Color::Light(BaseColor::Magenta))),
-                ActorType::Ghost => ("g", black_bg(Color::Dark(BaseColor::White))),
-                ActorType::Dragonfly => ("d", black_bg(Color::Light(BaseColor::Cyan))),
+        if let TileView::Visible { actor: Some(actor), wounded, .. } = view {
+            let black_bg = |color| if wounded {
+                ColorStyle::new(color, Color::Dark(BaseColor::Red))
+            } else {
+                black_bg(color)
             };
+            let def = actor.def();
+            return (def.glyph, black_bg(parse_color(&def.color)));
         }
         let (object, tile, vis) = match view {
             TileView::Visible { object, tile, .. } => (object, tile, true),
             TileView::Remembered { object, tile, .. } => (object, tile, false),
             TileView::Explorable => {
-                return ("?", black_bg(Color::Dark(BaseColor::Magenta)));
+                return ('?', black_bg(Color::Dark(BaseColor::Magenta)));
             }
             TileView::Unknown => {
-                return (" ", black_bg(Color::Dark(BaseColor::Black)));
+                return (' ', black_bg(Color::Dark(BaseColor::Black)));
             }
         };
         if let Some(object) = object {
-            let corpse = |c| black_bg(if vis {
-                Color::Light(c)
-            } else {
-                Color::Light(BaseColor::Black)
-            });
+            let corpse = |c| black_bg(if vis { c } else { Color::Light(BaseColor::Black) });
             return match object {
                 // TODO: handle Actor some other way?
-                EntityType::Actor(_) => ("!", corpse(BaseColor::Red)),
-                EntityType::Corpse(ActorType::BigJelly) | EntityType::Corpse(ActorType::LittleJelly) =>
-                    ("%", corpse(BaseColor::Magenta)),
-                EntityType::Corpse(ActorType::Beetle) | EntityType::Corpse(ActorType::Dragonfly) =>
-                    ("%", corpse(BaseColor::Cyan)),
-                EntityType::Corpse(_) => ("%", corpse(BaseColor::Red)),
+                EntityType::Actor(_) => ('!', corpse(Color::Light(BaseColor::Red))),
+                EntityType::Corpse(actor) => ('%', corpse(parse_color(&actor.def().corpse_color))),
+                EntityType::Item(ItemType::Meat) => ('*', corpse(Color::Light(BaseColor::Red))),
+                EntityType::Item(ItemType::Jelly) => ('*', corpse(Color::Light(BaseColor::Magenta))),
+                EntityType::Item(ItemType::Ectoplasm) => ('*', corpse(Color::Light(BaseColor::White))),
             };
         }
         let (ch, color) = match tile {
-            Tile::Wall => ("#", Color::Dark(BaseColor::Yellow)),
-            Tile::Tree => ("#", Color::Dark(BaseColor::Green)),
-            Tile::Ground => (".", Color::Light(BaseColor::Yellow)),
+            Tile::Wall => ('#', Color::Dark(BaseColor::Yellow)),
+            Tile::Tree => ('#', Color::Dark(BaseColor::Green)),
+            Tile::Ground => ('.', Color::Light(BaseColor::Yellow)),
+            Tile::StairsDown => ('>', Color::Light(BaseColor::White)),
+            Tile::StairsUp => ('<', Color::Light(BaseColor::White)),
+            Tile::ShallowWater => ('~', Color::Light(BaseColor::Cyan)),
+            Tile::DeepWater => ('~', Color::Dark(BaseColor::Blue)),
+            Tile::OpenDoor => ('\'', Color::Dark(BaseColor::Yellow)),
+            Tile::ClosedDoor => ('+', Color::Dark(BaseColor::Yellow)),
+            Tile::Rubble => (':', Color::Light(BaseColor::Yellow)),
         };
         let color = if vis { color } else { Color::Light(BaseColor::Black) };
         let color_style = if tile.obstruction() == Obstruction::Full {
@@ -112,78 +491,158 @@ impl GameMap {
         (ch, color_style)
     }
 
-    fn event_direction(ev: Event) -> Option<Direction> {
-        Some(match ev {
-            // arrow keys
-            Event::Key(Key::Up) => Direction::North,
-            Event::Key(Key::Down) => Direction::South,
-            Event::Key(Key::Left) => Direction::West,
-            Event::Key(Key::Right) => Direction::East,
-            // number keys
-            Event::Char('1') => Direction::SouthWest,
-            Event::Char('2') => Direction::South,
-            Event::Char('3') => Direction::SouthEast,
-            Event::Char('4') => Direction::West,
-            Event::Char('6') => Direction::East,
-            Event::Char('7') => Direction::NorthWest,
-            Event::Char('8') => Direction::North,
-            Event::Char('9') => Direction::NorthEast,
-            // vi keys
-            Event::Char('h') => Direction::West,
-            Event::Char('j') => Direction::South,
-            Event::Char('k') => Direction::North,
-            Event::Char('l') => Direction::East,
-            Event::Char('y') => Direction::NorthWest,
-            Event::Char('u') => Direction::NorthEast,
-            Event::Char('b') => Direction::SouthWest,
-            Event::Char('n') => Direction::SouthEast,
-            _ => { return None; }
-        })
+    /// Moves the view instead of the player, leaving it wherever it ends up.
+    fn pan(&mut self, dir: Direction) -> EventResult {
+        if self.camera_mode != CameraMode::Manual {
+            self.camera_mode = CameraMode::Manual;
+            let mut log = self.log.borrow_mut();
+            log.new_turn();
+            log.push(CameraMode::Manual.description().to_string());
+        }
+        if let Some(cam) = self.camera.get() {
+            self.camera.set(Some(cam.panned(dir)));
+        }
+        EventResult::Consumed(None)
     }
 }
 
 impl View for GameMap {
     fn draw(&self, pr: &Printer) {
         let game = self.game.borrow();
-        let player_pos = match game.player_position() {
-            Some(pos) => pos,
-            None => { return; }
-        };
-
-        // TODO: recenter camera if off screen
-        // TODO: manage screen resize
-        let camera = self.camera.get().unwrap_or_else(|| {
-            Camera::centered(pr.size, player_pos)
-        });
-        self.camera.set(Some(camera));
-        for x in 0..pr.size.x {
-            for y in 0..pr.size.y {
-                let pos = camera.map_position(Vec2 { x, y });
-                let (ch, color_style) = GameMap::render_tile(game.view(pos));
-                pr.with_color(color_style, |pr| {
-                    pr.print(Vec2::new(x, y), ch);
-                });
+        GameMap::draw_game(&game, &self.camera, self.camera_mode, pr, self.log.borrow().debug);
+        let cam = self.camera.get();
+        let lines = match (self.look, self.hover) {
+            (Some(pos), _) => {
+                if let Some(screen_pos) = cam.and_then(|cam| cam.screen_position(pos)) {
+                    let (ch, color_style) = GameMap::render_tile(game.view(pos));
+                    pr.with_color(color_style, |pr| pr.with_effect(Effect::Reverse, |pr| {
+                        pr.print(screen_pos, ch.encode_utf8(&mut [0; 4]));
+                    }));
+                }
+                let mut lines = messages::describe_look(game.view(pos));
+                lines.push(format!("({} or Esc to stop looking)", keymap().key(Command::Look)));
+                lines
             }
+            (None, Some(pos)) => vec![messages::describe_tile(game.view(pos))],
+            (None, None) => Vec::new(),
+        };
+        for (i, line) in lines.iter().rev().enumerate().take(pr.size.y) {
+            pr.print((0, pr.size.y - 1 - i), line);
         }
     }
 
     fn on_event(&mut self, ev: Event) -> EventResult {
-        let do_action = |action| {
-            let mut game = self.game.borrow_mut();
-            // TODO: log error?
-            let _ = game.take_player_action(action);
-            EventResult::Consumed(None)
+        if let Event::Mouse { offset, position, event } = ev {
+            return self.on_mouse(offset, position, event);
+        }
+        self.hover = None;
+        if let Some(pos) = self.look {
+            return self.look_around(pos, ev);
+        }
+        let command = match keymap().command(Context::Game, &ev) {
+            Some(command) => command,
+            None => { return EventResult::Ignored; }
         };
-        match ev {
-            Event::Char('5') => do_action(Action::Wait),
-            Event::Char('.') => do_action(Action::Wait),
-            Event::Char('R') => {
+        match command {
+            Command::Move(dir) => self.do_action(Action::MoveAttack(dir)),
+            Command::Wait => self.do_action(Action::Wait),
+            Command::PickUp => self.do_action(Action::PickUp),
+            Command::Harvest => self.do_action(Action::Harvest),
+            Command::CloseDoor => {
+                // close whichever open door is next to the player, if there is one
+                let dir = {
+                    let game = self.game.borrow();
+                    game.player_position().and_then(|pos| ALL_DIRECTIONS.iter().cloned()
+                        .find(|&dir| game.view(pos.step(dir)).tile() == Some(Tile::OpenDoor)))
+                };
+                match dir {
+                    Some(dir) => self.do_action(Action::CloseDoor(dir)),
+                    None => {
+                        let mut log = self.log.borrow_mut();
+                        log.new_turn();
+                        log.push("There's no open door here to close.".to_string());
+                        EventResult::Consumed(None)
+                    }
+                }
+            }
+            Command::Descend => self.do_action(Action::Descend),
+            Command::Ascend => self.do_action(Action::Ascend),
+            Command::Sneak => self.do_action(Action::ToggleSneak),
+            Command::Explore => self.travel(None),
+            Command::Inventory => {
+                let (game, log) = (self.game.clone(), self.log.clone());
+                EventResult::with_cb(move |s| s.add_layer(inventory_dialog(game.clone(), log.clone())))
+            }
+            Command::Look => {
+                self.look = self.game.borrow().player_position();
+                EventResult::Consumed(None)
+            }
+            Command::Pan(dir) => self.pan(dir),
+            Command::Camera => {
+                self.camera_mode = self.camera_mode.next();
+                let mut log = self.log.borrow_mut();
+                log.new_turn();
+                log.push(self.camera_mode.description().to_string());
+                EventResult::Consumed(None)
+            }
+            Command::Messages => {
+                let history = self.log.borrow().history();
+                EventResult::with_cb(move |s| {
+                    s.add_layer(Dialog::around(
+                        ScrollView::new(TextView::new(history.clone()))
+                            .scroll_strategy(ScrollStrategy::StickToBottom)
+                    ).title("Message history").dismiss_button("Close"));
+                })
+            }
+            Command::Help => EventResult::with_cb(|s| {
+                s.add_layer(Dialog::around(ScrollView::new(TextView::new(keymap().help())))
+                    .title("Keys").dismiss_button("Close"));
+            }),
+            Command::Practice => {
+                let mut game = self.game.borrow_mut();
+                let mut log = self.log.borrow_mut();
+                log.new_turn();
+                if game.is_practice() {
+                    log.push("You're already in practice mode.".to_string());
+                } else {
+                    game.set_practice(true);
+                    log.push(format!(
+                        "Practice mode: press {} to undo a turn or {} to undo ten. \
+                            Runs that use undo won't be scored.",
+                        keymap().key(Command::Undo), keymap().key(Command::UndoTen),
+                    ));
+                }
+                EventResult::Consumed(None)
+            }
+            Command::Undo => self.undo(1),
+            Command::UndoTen => self.undo(10),
+            Command::Restart => {
+                let mut game = self.game.borrow_mut();
+                let replay = game.replay();
+                if !replay.is_empty() {
+                    self.finished_runs.borrow_mut().push(replay);
+                }
+                game.restart();
                 self.camera.set(None);
-                self.game.borrow_mut().restart();
+                let mut log = self.log.borrow_mut();
+                log.new_turn();
+                log.push("You start a new game.".to_string());
+                drop(log);
+                self.log_events(&mut game);
                 EventResult::Consumed(None)
-            },
-            _ => GameMap::event_direction(ev).map(|dir| do_action(Action::MoveAttack(dir)))
-                .unwrap_or(EventResult::Ignored),
+            }
+            Command::Debug => {
+                let mut log = self.log.borrow_mut();
+                log.debug = !log.debug;
+                log.new_turn();
+                let msg = format!("AI debugging {}.", if log.debug { "on" } else { "off" });
+                log.push(msg);
+                EventResult::Consumed(None)
+            }
+            // quitting is a global callback, and the replay commands don't apply here
+            Command::Quit | Command::PauseReplay | Command::StepReplay | Command::FastForward => {
+                EventResult::Ignored
+            }
         }
     }
 
@@ -192,16 +651,192 @@ impl View for GameMap {
     }
 }
 
-pub fn build_ui(siv: &mut Cursive, seed: u64) {
-    let game = Rc::new(RefCell::new(Game::new(seed)));
+struct ReplayView {
+    playback: Playback,
+    camera: Cell<Option<Camera>>,
+    paused: bool,
+    fast_forward: bool,
+    debug: bool,
+    frame: u32,
+    result: Option<Result<(), ReplayError>>,
+}
+
+impl ReplayView {
+    // at 10 fps, this is a bit over 3 turns a second
+    const FRAMES_PER_STEP: u32 = 3;
+    const FAST_FORWARD_STEPS: u32 = 3;
+
+    fn step(&mut self) {
+        if self.result.is_some() {
+            return;
+        }
+        let depth = self.playback.game().depth();
+        let result = self.playback.step();
+        if self.playback.game().depth() != depth {
+            self.camera.set(None);
+        }
+        match result {
+            Some(Err(e)) => { self.result = Some(Err(e)); }
+            _ if self.playback.is_finished() => { self.result = Some(self.playback.verify()); }
+            _ => {}
+        }
+    }
+
+    fn status(&self) -> String {
+        let replay = self.playback.replay();
+        let state = match &self.result {
+            Some(Ok(())) => "finished, verified".to_string(),
+            Some(Err(e)) => format!("DESYNC: {:?}", e),
+            None if self.paused => "paused".to_string(),
+            None if self.fast_forward => "fast forward".to_string(),
+            None => "playing".to_string(),
+        };
+        format!(
+            "Replay of seed {} ({}){} - turn {}/{} - {} ({}: pause, {}: step, {}: fast forward, {}: AI states)",
+            replay.seed(), replay.map_kind().name(), if replay.is_scoring() { "" } else { " (practice)" },
+            self.playback.next_step(), replay.len(), state,
+            keymap().key(Command::PauseReplay), keymap().key(Command::StepReplay),
+            keymap().key(Command::FastForward), keymap().key(Command::Debug),
+        )
+    }
+}
+
+impl View for ReplayView {
+    fn draw(&self, pr: &Printer) {
+        GameMap::draw_game(self.playback.game(), &self.camera, CameraMode::Edge, &pr.shrinked((0, 1)), self.debug);
+        if pr.size.y > 0 {
+            pr.print((0, pr.size.y - 1), &self.status());
+        }
+    }
+
+    fn on_event(&mut self, ev: Event) -> EventResult {
+        if ev == Event::Refresh {
+            if !self.paused {
+                self.frame += 1;
+                if self.fast_forward {
+                    for _ in 0..ReplayView::FAST_FORWARD_STEPS {
+                        self.step();
+                    }
+                } else if self.frame >= ReplayView::FRAMES_PER_STEP {
+                    self.frame = 0;
+                    self.step();
+                }
+            }
+            return EventResult::Consumed(None);
+        }
+        match keymap().command(Context::Replay, &ev) {
+            Some(Command::PauseReplay) => { self.paused = !self.paused; }
+            Some(Command::StepReplay) => {
+                self.paused = true;
+                self.step();
+            }
+            Some(Command::FastForward) => {
+                self.paused = false;
+                self.fast_forward = !self.fast_forward;
+            }
+            Some(Command::Debug) => { self.debug = !self.debug; }
+            _ => { return EventResult::Ignored; }
+        }
+        EventResult::Consumed(None)
+    }
+
+    fn required_size(&mut self, _constraint: Vec2) -> Vec2 {
+        Vec2::new(11, 12)
+    }
+}
+
+pub fn build_ui(siv: &mut Cursive, seed: u64, map_kind: MapKind) {
+    build_game_ui(siv, Game::new_with_map(seed, map_kind));
+}
+
+pub fn build_ui_from_save(siv: &mut Cursive, save: &str) -> Result<(), LoadError> {
+    build_game_ui(siv, Game::load(save)?);
+    Ok(())
+}
+
+/// Quitting works whatever's on top, so it's set up ahead of time rather than handled by a view.
+fn add_quit_callbacks(siv: &mut Cursive) {
+    for event in keymap().events(Command::Quit) {
+        siv.add_global_callback(event, |s| s.quit());
+    }
+}
+
+pub fn build_replay_ui(siv: &mut Cursive, replay: Replay) {
+    add_quit_callbacks(siv);
+    siv.set_fps(10);
+
+    siv.add_fullscreen_layer(BoxView::with_full_screen(ReplayView {
+        playback: Playback::new(replay),
+        camera: Cell::new(None),
+        paused: false,
+        fast_forward: false,
+        debug: false,
+        frame: 0,
+        result: None,
+    }));
+}
+
+struct Session {
+    game: Rc<RefCell<Game>>,
+    finished_runs: Rc<RefCell<Vec<Replay>>>,
+}
+
+/// Returns the current game in save file format, or `None` if there's nothing worth resuming.
+pub fn save_game(siv: &mut Cursive) -> Option<String> {
+    let game = siv.user_data::<Session>()?.game.borrow();
+    if !game.is_player_alive() {
+        return None;
+    }
+    Some(game.save())
+}
+
+/// Returns replays of every run played in this session, including the current one.
+pub fn recorded_replays(siv: &mut Cursive) -> Vec<Replay> {
+    let session = match siv.user_data::<Session>() {
+        Some(session) => session,
+        None => { return Vec::new(); }
+    };
+    let mut replays = session.finished_runs.borrow().clone();
+    let current = session.game.borrow().replay();
+    if !current.is_empty() {
+        replays.push(current);
+    }
+    replays
+}
+
+fn build_game_ui(siv: &mut Cursive, game: Game) {
+    let game = Rc::new(RefCell::new(game));
+    let finished_runs = Rc::new(RefCell::new(Vec::new()));
+    let log = Rc::new(RefCell::new(MessageLog::default()));
+    log.borrow_mut().push(format!(
+        "Welcome to Scavenger! Press {} for help or {} to see old messages.",
+        keymap().key(Command::Help), keymap().key(Command::Messages),
+    ));
+    siv.set_user_data(Session {
+        game: game.clone(),
+        finished_runs: finished_runs.clone(),
+    });
+
+    add_quit_callbacks(siv);
 
-    siv.add_global_callback(Event::CtrlChar('q'), |s| s.quit());
+    let map = GameMap {
+        game: game.clone(),
+        camera: Cell::new(None),
+        camera_mode: CameraMode::Edge,
+        hover: None,
+        look: None,
+        finished_runs,
+        log: log.clone(),
+    };
+    map.log_events(&mut game.borrow_mut());
 
     siv.add_fullscreen_layer(BoxView::with_full_screen(
-        LinearLayout::new(Orientation::Vertical)
-            .child(BoxView::with_full_screen(GameMap {
-                game: game.clone(),
-                camera: Cell::new(None),
-            }))
+        LinearLayout::new(Orientation::Horizontal)
+            .child(BoxView::with_full_screen(
+                LinearLayout::new(Orientation::Vertical)
+                    .child(BoxView::with_full_screen(map))
+                    .child(BoxView::with_fixed_height(MessagePanel::HEIGHT, MessagePanel { log }))
+            ))
+            .child(BoxView::with_fixed_width(Sidebar::WIDTH, Sidebar { game }))
     ));
 }
@@ -1,6 +1,191 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use scavenger::KeymapError;
+use scavenger::cursive::{Cursive, views::Dialog};
+use scavenger::game::{MapKind, Replay, SpawnTable};
+
+fn data_dir() -> PathBuf {
+    let data_dir = env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_default();
+    data_dir.join("scavenger")
+}
+
+fn config_dir() -> PathBuf {
+    let config_dir = env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_default();
+    config_dir.join("scavenger")
+}
+
+fn save_path() -> PathBuf {
+    data_dir().join("save.ron")
+}
+
+/// Lets designers try out creature changes without rebuilding.
+fn load_creatures() {
+    let path = data_dir().join("creatures.ron");
+    if let Ok(creatures) = fs::read_to_string(&path) {
+        if let Err(e) = scavenger::game::load_creatures(&creatures) {
+            eprintln!("Invalid creatures in {}: {:?}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Lets players change which keys do what, see data/keymap.toml.
+fn load_keymap() {
+    let path = config_dir().join("keymap.toml");
+    if let Ok(keymap) = fs::read_to_string(&path) {
+        match scavenger::load_keymap(&keymap) {
+            Ok(()) => {}
+            Err(KeymapError::Conflicts(conflicts)) => {
+                eprintln!("Conflicting keys in {}:", path.display());
+                for (key, commands) in conflicts {
+                    eprintln!("  {} is bound to {}", key, commands.join(" and "));
+                }
+                process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Invalid keymap in {}: {:?}", path.display(), e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn write_file(path: PathBuf, contents: String) {
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if let Err(e) = fs::write(&path, contents) {
+        eprintln!("Failed to write {}: {}", path.display(), e);
+    }
+}
+
+fn read_replay(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {}", path, e);
+        process::exit(1);
+    })
+}
+
+fn offer_resume(siv: &mut Cursive, seed: u64, map_kind: MapKind, save: String) {
+    let path = save_path();
+    siv.add_layer(Dialog::text("Resume your saved game?")
+        .title("Scavenger")
+        .button("Resume", move |s| {
+            s.pop_layer();
+            match scavenger::build_ui_from_save(s, &save) {
+                // saves are only good for one resume
+                Ok(()) => { let _ = fs::remove_file(&path); }
+                Err(e) => {
+                    // keep it out of the way of the new game's save in case it can be recovered
+                    let kept = data_dir().join("unloadable-save.ron");
+                    let _ = fs::rename(&path, &kept);
+                    scavenger::build_ui(s, seed, map_kind);
+                    s.add_layer(Dialog::info(format!(
+                        "Could not load saved game: {:?}\nIt was kept at {}.", e, kept.display(),
+                    )));
+                }
+            }
+        })
+        .button("New game", move |s| {
+            s.pop_layer();
+            let _ = fs::remove_file(save_path());
+            scavenger::build_ui(s, seed, map_kind);
+        }));
+}
+
+fn usage() -> ! {
+    let maps: Vec<_> = MapKind::ALL.iter().map(|kind| kind.name()).collect();
+    eprintln!(
+        "usage: scavenger [--map {} | --replay FILE | --verify-replay FILE | --spawn-table DEPTH [TILES]]",
+        maps.join("|"),
+    );
+    process::exit(2);
+}
+
+/// Prints what a level's spawn table works out to on average, for balancing.
+fn print_spawn_table(depth: u32, tiles: usize) {
+    let table = SpawnTable::for_depth(depth);
+    let total_weight: u32 = table.entries().iter().map(|entry| entry.weight).sum();
+    println!("Depth {}, {} tiles, one group per {} tiles:", depth, tiles, table.tiles_per_group());
+    println!("{:<16}{:>8}{:>8}{:>8}{:>8}{:>10}", "creature", "weight", "group", "min", "max", "expected");
+    let mut total = 0.0;
+    for (entry, (_, expected)) in table.entries().iter().zip(table.expected_composition(tiles)) {
+        let max = entry.max_count.map_or("-".to_string(), |max| max.to_string());
+        println!(
+            "{:<16}{:>7.1}%{:>8}{:>8}{:>8}{:>10.1}",
+            entry.actor.name(), 100.0 * entry.weight as f64 / total_weight.max(1) as f64,
+            format!("{}-{}", entry.group_size.0, entry.group_size.1), entry.min_count, max, expected,
+        );
+        total += expected;
+    }
+    println!("{:<56}{:>10.1}", "total", total);
+}
+
 fn main() {
-    let siv = &mut cursive::Cursive::default();
-    let seed = rand::random();
-    scavenger::build_ui(siv, seed);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    load_creatures();
+    // tools which don't need a terminal
+    match args.as_slice() {
+        ["--verify-replay", path] => {
+            let result = Replay::parse(&read_replay(path))
+                .and_then(|replay| replay.verify());
+            match result {
+                Ok(()) => { println!("{}: ok", path); }
+                Err(e) => {
+                    println!("{}: {:?}", path, e);
+                    process::exit(1);
+                }
+            }
+            return;
+        }
+        ["--spawn-table", depth] | ["--spawn-table", depth, _] => {
+            let depth = depth.parse().unwrap_or_else(|_| usage());
+            let tiles = args.get(2).map_or(Ok(800), |tiles| tiles.parse()).unwrap_or_else(|_| usage());
+            print_spawn_table(depth, tiles);
+            return;
+        }
+        [] | ["--map", _] | ["--replay", _] => {}
+        _ => usage(),
+    }
+    let map_kind = match args.as_slice() {
+        ["--map", name] => MapKind::from_name(name).unwrap_or_else(|| usage()),
+        _ => MapKind::default(),
+    };
+    // exiting once the terminal has been taken over would leave it in a mess
+    let replay = match args.as_slice() {
+        ["--replay", path] => Some(Replay::parse(&read_replay(path)).unwrap_or_else(|e| {
+            eprintln!("Invalid replay {}: {:?}", path, e);
+            process::exit(1);
+        })),
+        _ => None,
+    };
+
+    load_keymap();
+    let siv = &mut Cursive::default();
+    if let Some(replay) = replay {
+        scavenger::build_replay_ui(siv, replay);
+    } else {
+        let seed = rand::random();
+        match fs::read_to_string(save_path()) {
+            Ok(save) => offer_resume(siv, seed, map_kind, save),
+            Err(_) => scavenger::build_ui(siv, seed, map_kind),
+        }
+    }
     siv.run();
+
+    if let Some(save) = scavenger::save_game(siv) {
+        write_file(save_path(), save);
+    }
+    for replay in scavenger::recorded_replays(siv) {
+        let path = data_dir().join("replays").join(format!("{}.ron", replay.seed()));
+        write_file(path, replay.serialize());
+    }
 }
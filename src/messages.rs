@@ -0,0 +1,362 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use cursive::{
+    Printer,
+    theme::{BaseColor, Color, ColorStyle},
+    vec::Vec2,
+    view::View,
+};
+
+use crate::game::{
+    Action,
+    ActionError,
+    ActorType,
+    EntityType,
+    GameEvent,
+    ItemType,
+    Movement,
+    Obstruction,
+    Tile,
+    TileView,
+};
+use crate::keymap::{Command, keymap};
+
+pub fn actor_name(actor: ActorType) -> &'static str {
+    if actor == ActorType::PLAYER { "you" } else { actor.name() }
+}
+
+pub fn item_name(item: ItemType) -> &'static str {
+    match item {
+        ItemType::Meat => "meat",
+        ItemType::Jelly => "jelly",
+        ItemType::Ectoplasm => "ectoplasm",
+    }
+}
+
+fn the(actor: ActorType) -> String {
+    match actor {
+        ActorType::PLAYER => "you".to_string(),
+        _ => format!("the {}", actor_name(actor)),
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// "a" or "an", for introducing something new.
+pub fn a(actor: ActorType) -> String {
+    let name = actor_name(actor);
+    let article = if name.starts_with(|c| "aeiou".contains(c)) { "an" } else { "a" };
+    format!("{} {}", article, name)
+}
+
+/// Picks the right form of a verb depending on whether the subject is the player.
+fn verb(actor: ActorType, second_person: &str, third_person: &str) -> String {
+    if actor == ActorType::PLAYER { second_person } else { third_person }.to_string()
+}
+
+/// Monsters changing their minds, which only matters when debugging the AI.
+pub fn describe_ai_changes(events: &[GameEvent]) -> Vec<String> {
+    events.iter().filter_map(|&event| match event {
+        GameEvent::AiChanged { actor, pos, from, to } => Some(format!(
+            "[ai] {} at ({}, {}): {} -> {}", actor_name(actor), pos.x, pos.y, from.name(), to.name()
+        )),
+        _ => None,
+    }).collect()
+}
+
+pub fn describe_events(events: &[GameEvent]) -> Vec<String> {
+    let mut messages = Vec::new();
+    // index of the message for the last attack, so a kill can replace it
+    let mut last_attack: Option<(usize, ActorType, ActorType)> = None;
+    // don't bother separately describing the knockback when a beetle crushes something
+    let mut crushed = false;
+    let knocked_back = |actor: ActorType| actor.def().pushed_on_hit;
+    for &event in events {
+        let msg = match event {
+            GameEvent::Moved { .. } | GameEvent::AiChanged { .. } => { continue; }
+            GameEvent::Attacked { attacker, target, .. } if knocked_back(attacker) => {
+                // beetles only ever hit things when they get knocked into them
+                crushed = true;
+                last_attack = Some((messages.len(), attacker, target));
+                format!("{} is knocked into {}.", the(attacker), the(target))
+            }
+            GameEvent::Attacked { attacker, target, .. } => {
+                last_attack = Some((messages.len(), attacker, target));
+                format!("{} {} {}.", the(attacker), verb(attacker, "hit", "hits"), the(target))
+            }
+            GameEvent::Killed { victim, killer: Some(killer), .. } => {
+                let msg = if knocked_back(killer) {
+                    format!(
+                        "{} is knocked into {}, crushing {}!",
+                        the(killer), the(victim), if victim == ActorType::PLAYER { "you" } else { "it" },
+                    )
+                } else {
+                    format!(
+                        "{} {} {}{}",
+                        the(killer), verb(killer, "kill", "kills"), the(victim),
+                        if victim == ActorType::PLAYER { "!" } else { "." },
+                    )
+                };
+                if let Some((idx, attacker, target)) = last_attack.take() {
+                    if idx + 1 == messages.len() && attacker == killer && target == victim {
+                        messages[idx] = capitalize(&msg);
+                        continue;
+                    }
+                }
+                msg
+            }
+            GameEvent::Killed { victim, killer: None, .. } => {
+                format!("{} {}.", the(victim), verb(victim, "die", "dies"))
+            }
+            GameEvent::Pushed { actor, .. } => {
+                if crushed {
+                    continue;
+                }
+                format!("{} is knocked back.", the(actor))
+            }
+            GameEvent::Split { actor, .. } => format!("{} splits!", the(actor)),
+            GameEvent::Revealed { actor, .. } => format!("{} appears right next to you!", a(actor)),
+            GameEvent::NoticedPlayer { actor, .. } => format!("{} notices you.", the(actor)),
+            GameEvent::LostTrack { actor, .. } => format!("{} loses track of you.", the(actor)),
+            GameEvent::Sneaking { sneaking: true } => "you start sneaking.".to_string(),
+            GameEvent::Sneaking { sneaking: false } => "you stop sneaking.".to_string(),
+            GameEvent::PickedUp { item } => format!("you pick up the {}.", item_name(item)),
+            GameEvent::Dropped { actor: ActorType::PLAYER, item, .. } => {
+                format!("you drop the {}.", item_name(item))
+            }
+            GameEvent::Dropped { actor, item, .. } => {
+                format!("{} leaves behind some {}.", the(actor), item_name(item))
+            }
+            GameEvent::Used { item: ItemType::Meat } => "you eat the meat and feel better.".to_string(),
+            GameEvent::Used { item: ItemType::Jelly } => {
+                "you eat the jelly and feel yourself speed up!".to_string()
+            }
+            GameEvent::Used { item: ItemType::Ectoplasm } => {
+                "you absorb the ectoplasm and feel completely restored.".to_string()
+            }
+            GameEvent::Harvested { corpse, item } => {
+                format!("you carve some {} from the {} corpse.", item_name(item), actor_name(corpse))
+            }
+            GameEvent::ChangedLevel { from, to } if to > from => {
+                format!("you descend the stairs to depth {}.", to)
+            }
+            GameEvent::ChangedLevel { to, .. } => format!("you climb back up to depth {}.", to),
+            GameEvent::OpenedDoor { actor, .. } => format!("{} {} the door.", the(actor), verb(actor, "open", "opens")),
+            GameEvent::ClosedDoor { actor, .. } => format!("{} {} the door.", the(actor), verb(actor, "close", "closes")),
+        };
+        messages.push(capitalize(&msg));
+    }
+    messages
+}
+
+fn tile_name(tile: Tile) -> &'static str {
+    match tile {
+        Tile::Wall => "wall",
+        Tile::Tree => "tree",
+        Tile::Ground => "ground",
+        Tile::StairsDown => "stairs down",
+        Tile::StairsUp => "stairs up",
+        Tile::ShallowWater => "shallow water",
+        Tile::DeepWater => "deep water",
+        Tile::OpenDoor => "open door",
+        Tile::ClosedDoor => "closed door",
+        Tile::Rubble => "rubble",
+    }
+}
+
+fn object_name(object: EntityType) -> String {
+    match object {
+        EntityType::Actor(actor) => a(actor),
+        EntityType::Corpse(actor) => format!("{} corpse", a(actor)),
+        EntityType::Item(item) => format!("some {}", item_name(item)),
+    }
+}
+
+/// A short summary of what the player knows about a tile.
+pub fn describe_tile(view: TileView) -> String {
+    let (actor, object, tile) = match view {
+        TileView::Visible { actor, wounded, object, tile } => {
+            let actor = actor.map(|actor| match actor {
+                ActorType::PLAYER => "you".to_string(),
+                _ if wounded => format!("{} (wounded)", a(actor)),
+                _ => a(actor),
+            });
+            (actor, object, tile)
+        }
+        TileView::Remembered { object, tile } => {
+            let things: Vec<_> = object.map(object_name).into_iter()
+                .chain(Some(tile_name(tile).to_string()))
+                .collect();
+            return format!("You remember {}.", things.join(", "));
+        }
+        TileView::Explorable => { return "You haven't seen what's there yet.".to_string(); }
+        TileView::Unknown => { return "You don't know what's there.".to_string(); }
+    };
+    let things: Vec<_> = actor.into_iter()
+        .chain(object.map(object_name))
+        .chain(Some(tile_name(tile).to_string()))
+        .collect();
+    format!("{}.", capitalize(&things.join(", ")))
+}
+
+/// How a creature gets around and what to watch out for when fighting it.
+fn describe_creature(actor: ActorType) -> Vec<String> {
+    let def = actor.def();
+    let player = ActorType::PLAYER.def();
+    let mut lines = vec![if def.armor > 0 {
+        format!("It has {} HP, hits for {} and has {} armor.", def.max_hp, def.damage, def.armor)
+    } else {
+        format!("It has {} HP and hits for {}.", def.max_hp, def.damage)
+    }];
+    if def.speed > player.speed {
+        lines.push("It's faster than you.".to_string());
+    } else if def.speed < player.speed {
+        lines.push("It's slower than you.".to_string());
+    }
+    match def.movement {
+        Movement::Any => {}
+        Movement::Orthogonal => {
+            lines.push("It can't move or attack diagonally, or be hit diagonally.".to_string());
+        }
+        Movement::Knight => {
+            lines.push("It jumps like a chess knight, over anything it can see past.".to_string());
+        }
+    }
+    if def.pushed_on_hit {
+        lines.push("Hitting it knocks it back, crushing whatever is behind it.".to_string());
+    }
+    if let Some(name) = &def.splits_into {
+        if let Some(split) = ActorType::named(name) {
+            lines.push(format!("It splits into {} on each side when it dies.", a(split)));
+        }
+    }
+    if def.invisible_until_adjacent {
+        lines.push("It can't be seen until it's right next to you.".to_string());
+    }
+    if def.swims {
+        lines.push("It swims through deep water.".to_string());
+    }
+    if def.passes_doors {
+        lines.push("It goes straight through closed doors.".to_string());
+    }
+    if def.flees_below > 0 {
+        lines.push("It runs away when badly hurt.".to_string());
+    }
+    lines
+}
+
+/// Everything the player knows about a tile, including the rules for whatever is on it.
+pub fn describe_look(view: TileView) -> Vec<String> {
+    let mut lines = vec![describe_tile(view)];
+    if let TileView::Visible { actor: Some(actor), .. } = view {
+        if actor != ActorType::PLAYER {
+            lines.extend(describe_creature(actor));
+        }
+    }
+    lines
+}
+
+/// Explains why an action failed, using what the player can see in the target direction.
+pub fn describe_error(action: Action, error: ActionError, target: TileView) -> String {
+    match error {
+        ActionError::IllegalDiagonal => match (action, target.actor()) {
+            (Action::Move(_), _) | (_, None) => "You can't squeeze diagonally between those walls.".to_string(),
+            (_, Some(actor)) if actor.def().movement == Movement::Orthogonal => {
+                format!("You can't hit {} diagonally.", the(actor))
+            }
+            (_, Some(actor)) => format!("You can't reach {} diagonally.", the(actor)),
+        },
+        ActionError::Impassible => match target.tile() {
+            Some(Tile::Tree) => "A tree blocks your way.".to_string(),
+            Some(Tile::DeepWater) => "The water is too deep to wade through.".to_string(),
+            Some(Tile::ClosedDoor) => "The door is closed.".to_string(),
+            Some(tile) if tile.obstruction() == Obstruction::Full => "A wall blocks your way.".to_string(),
+            _ => "Something blocks your way.".to_string(),
+        },
+        ActionError::Occupied => "Something is in the way.".to_string(),
+        ActionError::InvalidActor => {
+            format!("You are dead. Press {} to start a new game.", keymap().key(Command::Restart))
+        }
+        ActionError::NoValidTarget => "There's nothing there to attack.".to_string(),
+        ActionError::NoItem => match action {
+            Action::Harvest => "There's nothing here worth harvesting.".to_string(),
+            _ => "There's nothing here to pick up.".to_string(),
+        },
+        ActionError::InvalidItem => "You don't have that.".to_string(),
+        ActionError::InventoryFull => "You can't carry any more.".to_string(),
+        ActionError::NoStairs => match action {
+            Action::Ascend => "There are no stairs up here.".to_string(),
+            _ => "There are no stairs down here.".to_string(),
+        },
+        ActionError::NoDoor => match action {
+            Action::CloseDoor(_) => "There's no open door there to close.".to_string(),
+            _ => "There's no closed door there to open.".to_string(),
+        },
+    }
+}
+
+#[derive(Default)]
+pub struct MessageLog {
+    messages: Vec<String>,
+    // messages from before this index were from previous turns
+    turn_start: usize,
+    /// Also log what monsters are thinking.
+    pub debug: bool,
+}
+
+impl MessageLog {
+    pub fn new_turn(&mut self) {
+        self.turn_start = self.messages.len();
+    }
+
+    pub fn push(&mut self, msg: String) {
+        self.messages.push(msg);
+    }
+
+    pub fn history(&self) -> String {
+        self.messages.join("\n")
+    }
+}
+
+pub struct MessagePanel {
+    pub log: Rc<RefCell<MessageLog>>,
+}
+
+impl MessagePanel {
+    pub const HEIGHT: usize = 4;
+}
+
+impl View for MessagePanel {
+    fn draw(&self, pr: &Printer) {
+        let log = self.log.borrow();
+        let black = ColorStyle::new(Color::Dark(BaseColor::Black), Color::Dark(BaseColor::Black));
+        pr.with_color(black, |pr| {
+            for y in 0..pr.size.y {
+                pr.print_hline((0, y), pr.size.x, " ");
+            }
+        });
+        let shown = log.messages.len().min(pr.size.y);
+        let first = log.messages.len() - shown;
+        for (y, msg) in log.messages[first..].iter().enumerate() {
+            let color = if first + y >= log.turn_start {
+                Color::Light(BaseColor::White)
+            } else {
+                Color::Light(BaseColor::Black)
+            };
+            pr.with_color(ColorStyle::new(color, Color::Dark(BaseColor::Black)), |pr| {
+                pr.print((0, y), msg);
+            });
+        }
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        Vec2::new(constraint.x, MessagePanel::HEIGHT)
+    }
+}
@@ -0,0 +1,95 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use cursive::{
+    Printer,
+    theme::{BaseColor, Color, ColorStyle},
+    vec::Vec2,
+    view::View,
+};
+
+use crate::game::{AiState, Game};
+use crate::messages;
+
+/// How the run is going and what's in sight, next to the map.
+pub struct Sidebar {
+    pub game: Rc<RefCell<Game>>,
+}
+
+impl Sidebar {
+    pub const WIDTH: usize = 22;
+}
+
+fn ai_state_name(state: AiState) -> &'static str {
+    match state {
+        AiState::Wait => "asleep",
+        AiState::Wander => "wandering",
+        AiState::Guard => "on guard",
+        AiState::Pursue => "hunting",
+        AiState::Search => "searching",
+        AiState::Flee => "fleeing",
+    }
+}
+
+impl View for Sidebar {
+    fn draw(&self, pr: &Printer) {
+        let game = self.game.borrow();
+        let black_bg = |color| ColorStyle::new(color, Color::Dark(BaseColor::Black));
+        let text = black_bg(Color::Light(BaseColor::White));
+        let heading = black_bg(Color::Light(BaseColor::Yellow));
+        let dim = black_bg(Color::Light(BaseColor::Black));
+        pr.with_color(text, |pr| {
+            for y in 0..pr.size.y {
+                pr.print_hline((0, y), pr.size.x, " ");
+            }
+        });
+
+        let mut y = 0;
+        let (seen, walkable) = game.explored();
+        let explored = (seen * 100).checked_div(walkable).unwrap_or(100);
+        pr.with_color(text, |pr| {
+            pr.print((1, y), &format!("Turn {}", game.turns()));
+            pr.print((1, y + 1), &format!("Seed {}", game.seed()));
+            pr.print((1, y + 2), &format!("Explored {}%", explored));
+        });
+        y += 4;
+
+        pr.with_color(heading, |pr| pr.print((1, y), "Kills"));
+        y += 1;
+        let kills = game.kills();
+        if kills.is_empty() {
+            pr.with_color(dim, |pr| pr.print((2, y), "none yet"));
+            y += 1;
+        }
+        for (actor, count) in kills {
+            pr.with_color(text, |pr| {
+                pr.print((2, y), &format!("{:>3} {}", count, messages::actor_name(actor)));
+            });
+            y += 1;
+        }
+        y += 1;
+
+        pr.with_color(heading, |pr| pr.print((1, y), "In view"));
+        y += 1;
+        let monsters = game.visible_monsters();
+        if monsters.is_empty() {
+            pr.with_color(dim, |pr| pr.print((2, y), "nothing"));
+        }
+        for (_, actor, state) in monsters {
+            let def = actor.def();
+            pr.with_color(black_bg(crate::parse_color(&def.color)), |pr| {
+                pr.print((2, y), def.glyph.encode_utf8(&mut [0; 4]));
+            });
+            pr.with_color(text, |pr| pr.print((4, y), messages::actor_name(actor)));
+            pr.with_color(dim, |pr| {
+                let name_width = messages::actor_name(actor).len();
+                pr.print((5 + name_width, y), ai_state_name(state));
+            });
+            y += 1;
+        }
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        Vec2::new(Sidebar::WIDTH, constraint.y)
+    }
+}
@@ -1,4 +1,4 @@
-const { Game } = wasm_bindgen;
+const { Game, load_keymap } = wasm_bindgen;
 
 const WIDTH = 80;
 const HEIGHT = 36;
@@ -68,6 +68,17 @@ const initDisplay = () => {
         requestAnimationFrame(update);
     });
 
+    let lastMousePos = null;
+    display.getContainer().addEventListener("mousemove", e => {
+        if (game == null) { return; }
+        const pos = display.eventToPosition(e);
+        // only bother when the mouse moves to a different cell
+        if (lastMousePos != null && pos[0] == lastMousePos[0] && pos[1] == lastMousePos[1]) { return; }
+        lastMousePos = pos;
+        game.push_mouse_move_event(pos[0], pos[1], e.buttons);
+        requestAnimationFrame(update);
+    });
+
     let minDelta = 1e9;
     display.getContainer().addEventListener("wheel", e => {
         minDelta = Math.min(minDelta, Math.abs(e.deltaY));
@@ -84,7 +95,16 @@ const initDisplay = () => {
 wasm_bindgen('./scavenger_wasm_bg.wasm').then(() => {
     const seed = Math.floor(Math.random() * Math.pow(2, 32));
     // console.log("game seed: " + seed);
-    game = Game.new(seed);
+    const map = new URLSearchParams(window.location.search).get("map") || "basin";
+    const keymap = localStorage.getItem("keymap");
+    if (keymap != null) {
+        try {
+            load_keymap(keymap);
+        } catch (e) {
+            console.error("invalid keymap: " + e);
+        }
+    }
+    game = Game.new(seed, map);
     game.set_size(WIDTH, HEIGHT);
     requestAnimationFrame(update);
 });
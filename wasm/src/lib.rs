@@ -192,9 +192,17 @@ fn rgb_to_u32(rgb: [u8; 3]) -> u32 {
     (rgb[0] as u32) + ((rgb[1] as u32) << 8) + ((rgb[2] as u32) << 16)
 }
 
+/// Changes which keys do what, in the same format as data/keymap.toml. This has to happen before
+/// the game is created.
+#[wasm_bindgen]
+pub fn load_keymap(keymap: &str) -> Result<(), JsValue> {
+    scavenger::load_keymap(keymap).map_err(|e| JsValue::from_str(&format!("{:?}", e)))
+}
+
 #[wasm_bindgen]
 impl Game {
-    pub fn new(seed: u32) -> Game {
+    /// `map` is the name of a map generator, falling back to the default for unknown names.
+    pub fn new(seed: u32, map: &str) -> Game {
         let term = Rc::new(RefCell::new(FakeTerminal::new()));
         let backend = FakeTerminalBackend {
             colors: Cell::new(ColorPair {
@@ -205,7 +213,8 @@ impl Game {
         };
         let mut siv = Cursive::new(|| Box::new(backend));
         // TODO: hide quit button
-        scavenger::build_ui(&mut siv, seed as u64);
+        let map_kind = scavenger::game::MapKind::from_name(map).unwrap_or_default();
+        scavenger::build_ui(&mut siv, seed as u64, map_kind);
         Game { terminal: term, ui: siv }
     }
 
@@ -248,7 +257,7 @@ impl Game {
 
     // TODO: drop ctrl+alt+shift instead of mapping it to ctrl+alt?
     pub fn push_keydown_event(&self, key_code: u32, ctrl: bool, alt: bool, shift: bool) {
-        key_code_to_key(key_code).map(|key| {
+        if let Some(key) = key_code_to_key(key_code) {
             self.push_event(match (ctrl, alt, shift) {
                 (false, false, false) => Event::Key(key),
                 (true, true, _) => Event::CtrlAlt(key),
@@ -258,18 +267,18 @@ impl Game {
                 (false, true, false) => Event::Alt(key),
                 (false, false, true) => Event::Shift(key),
             });
-        });
+        }
     }
 
     // TODO: drop ctrl+alt instead of mapping it to ctrl?
     pub fn push_keypress_event(&self, char_code: u32, ctrl: bool, alt: bool) {
-        std::char::from_u32(char_code).map(|ch| {
+        if let Some(ch) = std::char::from_u32(char_code) {
             self.push_event(match (ctrl, alt) {
                 (false, false) => Event::Char(ch),
                 (true, _) => Event::CtrlChar(ch),
                 (false, true) => Event::AltChar(ch),
             });
-        });
+        }
     }
 
     pub fn push_mouse_press_event(&self, x: usize, y: usize, button: u32) {
@@ -311,7 +320,28 @@ impl Game {
         }
     }
 
-    // TODO: implement MouseEvent::Hold support
+    /// Cursive has no event for the mouse just moving, so moving it without holding any buttons
+    /// down counts as holding `MouseButton::Other`.
+    pub fn push_mouse_move_event(&self, x: usize, y: usize, buttons: u32) {
+        // bits of MouseEvent.buttons, which don't match the numbering of MouseEvent.button
+        let button = if buttons & 1 != 0 {
+            MouseButton::Left
+        } else if buttons & 2 != 0 {
+            MouseButton::Right
+        } else if buttons & 4 != 0 {
+            MouseButton::Middle
+        } else {
+            MouseButton::Other
+        };
+        let mut term = self.terminal.borrow_mut();
+        if x < term.width && y < term.height {
+            term.events.push_back(Event::Mouse {
+                offset: Vec2 { x: 0, y: 0 },
+                position: Vec2 { x, y },
+                event: MouseEvent::Hold(button),
+            });
+        }
+    }
 
     pub fn push_mouse_wheel_event(&self, x: usize, y: usize, delta: i32) {
         let event = Event::Mouse {